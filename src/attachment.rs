@@ -0,0 +1,363 @@
+// Attachment module
+//
+// Binaries live in the KDBX 4 inner header, each stored once however many
+// entries use it. The keepass crate does not read or write the `<Binary>`
+// references of entries, so each attachment is referenced from an entry
+// CustomData item `KeedaVault.Attachment.<name>` holding the SHA-256 of its
+// content. Other KeePass clients do not show these attachments.
+//
+// Binaries stored by us are listed in the Meta CustomData item
+// `KeedaVault.Attachments`. Only those are dropped once unreferenced: the
+// `<Binary>` references of other clients are lost on load, so their binaries
+// cannot be told apart from unused ones and are always kept.
+
+use crate::crypto::CryptoUtils;
+use crate::storage;
+use keepass::db::{
+    CustomDataItem, Entry as KpEntry, Group as KpGroup, HeaderAttachment, Meta, Node, Value,
+};
+use keepass::Database;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Prefix of the entry CustomData keys referencing attachments
+pub const ATTACHMENT_PREFIX: &str = "KeedaVault.Attachment.";
+
+/// Meta CustomData key listing the content hashes of binaries we stored
+pub const MANAGED_KEY: &str = "KeedaVault.Attachments";
+
+/// Inner header flag marking a binary as protected in memory
+const PROTECTED_FLAG: u8 = 0x01;
+
+/// File attached to an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// File name, unique within the entry
+    pub name: String,
+
+    /// Size in bytes
+    pub size: usize,
+
+    /// Whether clients should keep the content in protected memory
+    pub protected: bool,
+}
+
+/// Attachments of an entry, sorted by name
+pub(crate) fn list(database: &Database, entry: &KpEntry) -> Vec<Attachment> {
+    let mut attachments: Vec<Attachment> = references(entry)
+        .filter_map(|(name, hash)| {
+            let binary = find_binary(database, hash)?;
+            Some(Attachment {
+                name: name.to_string(),
+                size: binary.content.len(),
+                protected: binary.flags & PROTECTED_FLAG != 0,
+            })
+        })
+        .collect();
+    attachments.sort_by(|a, b| a.name.cmp(&b.name));
+    attachments
+}
+
+/// Content of the attachment called `name`
+pub(crate) fn read<'a>(database: &'a Database, entry: &KpEntry, name: &str) -> Option<&'a [u8]> {
+    let hash = reference(entry, name)?;
+    find_binary(database, hash).map(|binary| binary.content.as_slice())
+}
+
+/// Attach `data` as `name`, replacing an attachment with the same name
+///
+/// Identical content is stored once. A binary is protected if any
+/// attachment using it asked for protection.
+///
+/// Returns the content hash if a new binary was stored, to be passed to
+/// `track`.
+pub(crate) fn attach(
+    binaries: &mut Vec<HeaderAttachment>,
+    entry: &mut KpEntry,
+    name: &str,
+    data: Vec<u8>,
+    protected: bool,
+) -> Option<String> {
+    let hash = content_hash(&data);
+    let flags = if protected { PROTECTED_FLAG } else { 0 };
+
+    let added = match binaries.iter_mut().find(|binary| binary.content == data) {
+        Some(existing) => {
+            existing.flags |= flags;
+            None
+        }
+        None => {
+            binaries.push(HeaderAttachment {
+                flags,
+                content: data,
+            });
+            Some(hash.clone())
+        }
+    };
+
+    entry.custom_data.items.insert(
+        key(name),
+        CustomDataItem {
+            value: Some(Value::Unprotected(hash)),
+            last_modification_time: Some(keepass::db::Times::now()),
+        },
+    );
+
+    added
+}
+
+/// Record that we stored the binary with content hash `hash`
+pub(crate) fn track(meta: &mut Meta, hash: String) {
+    let mut managed = managed(meta);
+    if managed.insert(hash) {
+        set_managed(meta, &managed);
+    }
+}
+
+/// Remove the reference to `name`, returning false if there is none
+///
+/// The binary itself is dropped by `remove_unused` once nothing uses it.
+pub(crate) fn detach(entry: &mut KpEntry, name: &str) -> bool {
+    entry.custom_data.items.remove(&key(name)).is_some()
+}
+
+/// Rename an attachment, returning false if `name` does not exist
+pub(crate) fn rename(entry: &mut KpEntry, name: &str, new_name: &str) -> bool {
+    match entry.custom_data.items.remove(&key(name)) {
+        Some(mut item) => {
+            item.last_modification_time = Some(keepass::db::Times::now());
+            entry.custom_data.items.insert(key(new_name), item);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Check if an entry has an attachment called `name`
+pub(crate) fn exists(entry: &KpEntry, name: &str) -> bool {
+    reference(entry, name).is_some()
+}
+
+/// Remove binaries we stored that no entry or history item refers to
+///
+/// Binaries of other clients are kept. Returns the number of removed
+/// binaries.
+pub(crate) fn remove_unused(database: &mut Database) -> usize {
+    let mut used = HashSet::new();
+    collect_references(&database.root, &mut used);
+    let managed = managed(&database.meta);
+
+    let binaries = &mut database.header_attachments;
+    let before = binaries.len();
+    let mut stored = BTreeSet::new();
+    binaries.retain(|binary| {
+        let hash = content_hash(&binary.content);
+        let keep = !managed.contains(&hash) || used.contains(&hash);
+        if keep && managed.contains(&hash) {
+            stored.insert(hash);
+        }
+        keep
+    });
+    let removed = before - binaries.len();
+
+    // Forget binaries that are gone
+    if stored != managed {
+        set_managed(&mut database.meta, &stored);
+    }
+
+    removed
+}
+
+/// Copy binaries from `source` that `target` does not have yet
+pub(crate) fn copy_missing(target: &mut Database, source: &Database) {
+    let source_managed = managed(&source.meta);
+    for binary in &source.header_attachments {
+        if !target
+            .header_attachments
+            .iter()
+            .any(|b| b.content == binary.content)
+        {
+            target.header_attachments.push(binary.clone());
+
+            let hash = content_hash(&binary.content);
+            if source_managed.contains(&hash) {
+                track(&mut target.meta, hash);
+            }
+        }
+    }
+}
+
+/// Total size of the attachments referenced by an entry
+///
+/// `sizes` maps content hashes to sizes, see `sizes`.
+pub(crate) fn total_size(entry: &KpEntry, sizes: &HashMap<String, usize>) -> usize {
+    references(entry)
+        .filter_map(|(_, hash)| sizes.get(hash))
+        .sum()
+}
+
+/// Size of each binary by content hash
+pub(crate) fn sizes(binaries: &[HeaderAttachment]) -> HashMap<String, usize> {
+    binaries
+        .iter()
+        .map(|binary| (content_hash(&binary.content), binary.content.len()))
+        .collect()
+}
+
+/// Content hashes of the binaries we stored
+fn managed(meta: &Meta) -> BTreeSet<String> {
+    match meta
+        .custom_data
+        .items
+        .get(MANAGED_KEY)
+        .and_then(|item| item.value.as_ref())
+    {
+        Some(Value::Unprotected(hashes)) => hashes.split_whitespace().map(String::from).collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+fn set_managed(meta: &mut Meta, managed: &BTreeSet<String>) {
+    if managed.is_empty() {
+        meta.custom_data.items.remove(MANAGED_KEY);
+        return;
+    }
+
+    let hashes: Vec<&str> = managed.iter().map(String::as_str).collect();
+    meta.custom_data.items.insert(
+        MANAGED_KEY.to_string(),
+        CustomDataItem {
+            value: Some(Value::Unprotected(hashes.join(" "))),
+            last_modification_time: Some(keepass::db::Times::now()),
+        },
+    );
+}
+
+fn collect_references(group: &KpGroup, used: &mut HashSet<String>) {
+    for child in &group.children {
+        match child {
+            Node::Group(child_group) => collect_references(child_group, used),
+            Node::Entry(entry) => {
+                let versions = entry.history.iter().flat_map(|h| h.get_entries());
+                for version in std::iter::once(entry).chain(versions) {
+                    used.extend(references(version).map(|(_, hash)| hash.to_string()));
+                }
+            }
+        }
+    }
+}
+
+/// Attachment names and content hashes referenced by an entry
+fn references(entry: &KpEntry) -> impl Iterator<Item = (&str, &str)> {
+    entry.custom_data.items.iter().filter_map(|(key, item)| {
+        let name = key.strip_prefix(ATTACHMENT_PREFIX)?;
+        match &item.value {
+            Some(Value::Unprotected(hash)) => Some((name, hash.as_str())),
+            _ => None,
+        }
+    })
+}
+
+fn reference<'a>(entry: &'a KpEntry, name: &str) -> Option<&'a str> {
+    match &entry.custom_data.items.get(&key(name))?.value {
+        Some(Value::Unprotected(hash)) => Some(hash),
+        _ => None,
+    }
+}
+
+fn find_binary<'a>(database: &'a Database, hash: &str) -> Option<&'a HeaderAttachment> {
+    database
+        .header_attachments
+        .iter()
+        .find(|binary| content_hash(&binary.content) == hash)
+}
+
+fn key(name: &str) -> String {
+    format!("{}{}", ATTACHMENT_PREFIX, name)
+}
+
+fn content_hash(data: &[u8]) -> String {
+    storage::to_hex(&CryptoUtils::sha256(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_binaries_are_stored_once() {
+        let mut database = Database::new(Default::default());
+        let mut first = KpEntry::new();
+        let mut second = KpEntry::new();
+
+        attach(
+            &mut database.header_attachments,
+            &mut first,
+            "cert.pem",
+            b"CERT".to_vec(),
+            false,
+        );
+        attach(
+            &mut database.header_attachments,
+            &mut second,
+            "copy.pem",
+            b"CERT".to_vec(),
+            true,
+        );
+        assert_eq!(database.header_attachments.len(), 1);
+        assert_eq!(read(&database, &first, "cert.pem"), Some(&b"CERT"[..]));
+        assert!(list(&database, &first)[0].protected);
+
+        assert!(rename(&mut second, "copy.pem", "renamed.pem"));
+        assert!(exists(&second, "renamed.pem"));
+        assert!(!detach(&mut second, "copy.pem"));
+    }
+
+    #[test]
+    fn test_remove_unused_binaries() {
+        let mut database = Database::new(Default::default());
+        let mut entry = KpEntry::new();
+        for (name, data) in [("a.txt", b"A"), ("b.txt", b"B")] {
+            let hash = attach(
+                &mut database.header_attachments,
+                &mut entry,
+                name,
+                data.to_vec(),
+                false,
+            );
+            track(&mut database.meta, hash.unwrap());
+        }
+        assert!(detach(&mut entry, "b.txt"));
+        database.root.add_child(entry);
+
+        assert_eq!(remove_unused(&mut database), 1);
+        assert_eq!(database.header_attachments.len(), 1);
+        assert_eq!(database.header_attachments[0].content, b"A");
+        assert_eq!(managed(&database.meta).len(), 1);
+    }
+
+    #[test]
+    fn test_foreign_binaries_are_kept() {
+        // Another client's attachment, whose entry reference keepass dropped
+        let mut database = Database::new(Default::default());
+        database.header_attachments.push(HeaderAttachment {
+            flags: 0,
+            content: b"foreign".to_vec(),
+        });
+
+        // Reusing its content does not make it ours
+        let mut entry = KpEntry::new();
+        let hash = attach(
+            &mut database.header_attachments,
+            &mut entry,
+            "copy.txt",
+            b"foreign".to_vec(),
+            false,
+        );
+        assert_eq!(hash, None);
+        assert!(detach(&mut entry, "copy.txt"));
+        database.root.add_child(entry);
+
+        assert_eq!(remove_unused(&mut database), 0);
+        assert_eq!(database.header_attachments.len(), 1);
+    }
+}
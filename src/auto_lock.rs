@@ -0,0 +1,114 @@
+// Auto-lock policy module
+//
+// Tracks idle and session timeouts inside the core so every frontend locks
+// the vault under the same rules.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Platform events that can lock the vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockTrigger {
+    /// The OS screen was locked
+    ScreenLocked,
+    /// The app moved to the background
+    AppBackgrounded,
+    /// The system is going to sleep
+    SystemSleep,
+    /// The active OS user changed
+    UserSwitched,
+}
+
+/// Auto-lock policy
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoLockPolicy {
+    /// Lock after this long without vault activity
+    pub idle_timeout: Option<Duration>,
+
+    /// Lock this long after unlocking, regardless of activity
+    pub session_timeout: Option<Duration>,
+
+    /// Platform events that lock the vault immediately
+    pub lock_on: Vec<LockTrigger>,
+}
+
+impl AutoLockPolicy {
+    /// Check if the policy has any timeout
+    pub fn has_timeout(&self) -> bool {
+        self.idle_timeout.is_some() || self.session_timeout.is_some()
+    }
+
+    /// Check if a platform event should lock the vault
+    pub fn locks_on(&self, trigger: LockTrigger) -> bool {
+        self.lock_on.contains(&trigger)
+    }
+
+    /// Time left before the policy locks the vault (None without timeouts)
+    pub(crate) fn remaining(
+        &self,
+        unlocked_at: Instant,
+        last_activity: Instant,
+        now: Instant,
+    ) -> Option<Duration> {
+        let idle = self
+            .idle_timeout
+            .map(|t| t.saturating_sub(now.saturating_duration_since(last_activity)));
+        let session = self
+            .session_timeout
+            .map(|t| t.saturating_sub(now.saturating_duration_since(unlocked_at)));
+
+        match (idle, session) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Check if the policy has expired
+    pub(crate) fn is_expired(
+        &self,
+        unlocked_at: Instant,
+        last_activity: Instant,
+        now: Instant,
+    ) -> bool {
+        self.remaining(unlocked_at, last_activity, now) == Some(Duration::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_timeout_never_expires() {
+        let policy = AutoLockPolicy::default();
+        let start = Instant::now();
+        let later = start + Duration::from_secs(3600);
+
+        assert_eq!(policy.remaining(start, start, later), None);
+        assert!(!policy.is_expired(start, start, later));
+    }
+
+    #[test]
+    fn test_shortest_timeout_wins() {
+        let policy = AutoLockPolicy {
+            idle_timeout: Some(Duration::from_secs(60)),
+            session_timeout: Some(Duration::from_secs(300)),
+            lock_on: vec![LockTrigger::ScreenLocked],
+        };
+        let start = Instant::now();
+
+        // Recent activity keeps the idle timer fresh, the session timer still runs
+        let now = start + Duration::from_secs(280);
+        let active = start + Duration::from_secs(270);
+        assert_eq!(
+            policy.remaining(start, active, now),
+            Some(Duration::from_secs(20))
+        );
+
+        let now = start + Duration::from_secs(300);
+        assert!(policy.is_expired(start, active, now));
+
+        assert!(policy.locks_on(LockTrigger::ScreenLocked));
+        assert!(!policy.locks_on(LockTrigger::AppBackgrounded));
+    }
+}
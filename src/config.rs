@@ -0,0 +1,280 @@
+// Database settings module
+//
+// Maps our `VaultConfig` onto the KDBX outer header settings used by keepass.
+
+use crate::error::{Result, VaultError};
+use keepass::config::{
+    CompressionConfig, DatabaseConfig, InnerCipherConfig, KdfConfig, OuterCipherConfig,
+};
+use serde::{Deserialize, Serialize};
+
+/// Key derivation function used to transform the composite key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KdfAlgorithm {
+    /// Argon2d (KDBX 4 default in KeePass)
+    Argon2d,
+    /// Argon2id (KDBX 4, side-channel resistant)
+    Argon2id,
+    /// AES-KDF (KDBX 3.1 compatible)
+    AesKdf,
+}
+
+/// Cipher used for the outer encryption of the database file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OuterCipher {
+    Aes256,
+    ChaCha20,
+    Twofish,
+}
+
+/// Cipher used for protected values inside the database (inner stream)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InnerCipher {
+    ChaCha20,
+    Salsa20,
+    Plain,
+}
+
+/// Compression applied to the inner XML payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    None,
+    GZip,
+}
+
+/// Largest Argon2 memory setting in KB (4 GB, the most KeePassXC allows)
+pub const MAX_ARGON2_MEMORY: u64 = 4 * 1024 * 1024;
+
+/// Vault configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultConfig {
+    /// Key derivation function
+    pub kdf: KdfAlgorithm,
+
+    /// KDF iterations (Argon2 passes or AES-KDF rounds)
+    pub kdf_iterations: u64,
+
+    /// Argon2 memory (in KB, ignored by AES-KDF)
+    pub argon2_memory: u64,
+
+    /// Argon2 parallelism (ignored by AES-KDF)
+    pub argon2_parallelism: u32,
+
+    /// Outer encryption cipher
+    pub cipher: OuterCipher,
+
+    /// Inner stream cipher for protected fields
+    pub inner_cipher: InnerCipher,
+
+    /// Payload compression
+    pub compression: Compression,
+}
+
+impl Default for VaultConfig {
+    fn default() -> Self {
+        Self {
+            kdf: KdfAlgorithm::Argon2id,
+            kdf_iterations: 2,
+            argon2_memory: 64 * 1024, // 64 MB
+            argon2_parallelism: 2,
+            cipher: OuterCipher::Aes256,
+            inner_cipher: InnerCipher::ChaCha20,
+            compression: Compression::GZip,
+        }
+    }
+}
+
+impl VaultConfig {
+    /// Check that the KDF parameters can be used to derive a key
+    pub fn validate(&self) -> Result<()> {
+        if self.kdf_iterations == 0 {
+            return Err(VaultError::InvalidConfig(
+                "KDF iterations must be at least 1".to_string(),
+            ));
+        }
+
+        if self.kdf != KdfAlgorithm::AesKdf {
+            if self.argon2_parallelism == 0 {
+                return Err(VaultError::InvalidConfig(
+                    "Argon2 parallelism must be at least 1".to_string(),
+                ));
+            }
+
+            // Argon2 requires at least 8 KB of memory per lane
+            if self.argon2_memory < 8 * self.argon2_parallelism as u64 {
+                return Err(VaultError::InvalidConfig(format!(
+                    "Argon2 memory must be at least {} KB for parallelism {}",
+                    8 * self.argon2_parallelism as u64,
+                    self.argon2_parallelism
+                )));
+            }
+
+            if self.argon2_memory > MAX_ARGON2_MEMORY {
+                return Err(VaultError::InvalidConfig(format!(
+                    "Argon2 memory must be at most {} KB",
+                    MAX_ARGON2_MEMORY
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build the keepass database config for these settings
+    ///
+    /// Settings we don't model (e.g. public custom data) are taken from `base`.
+    pub(crate) fn to_database_config(&self, base: &DatabaseConfig) -> Result<DatabaseConfig> {
+        self.validate()?;
+
+        let kdf_config = match self.kdf {
+            KdfAlgorithm::Argon2d => KdfConfig::Argon2 {
+                iterations: self.kdf_iterations,
+                memory: self.argon2_memory * 1024,
+                parallelism: self.argon2_parallelism,
+                version: rust_argon2::Version::Version13,
+            },
+            KdfAlgorithm::Argon2id => KdfConfig::Argon2id {
+                iterations: self.kdf_iterations,
+                memory: self.argon2_memory * 1024,
+                parallelism: self.argon2_parallelism,
+                version: rust_argon2::Version::Version13,
+            },
+            KdfAlgorithm::AesKdf => KdfConfig::Aes {
+                rounds: self.kdf_iterations,
+            },
+        };
+
+        Ok(DatabaseConfig {
+            // Always write KDBX 4, which is the only version keepass can save
+            version: DatabaseConfig::default().version,
+            outer_cipher_config: match self.cipher {
+                OuterCipher::Aes256 => OuterCipherConfig::AES256,
+                OuterCipher::ChaCha20 => OuterCipherConfig::ChaCha20,
+                OuterCipher::Twofish => OuterCipherConfig::Twofish,
+            },
+            compression_config: match self.compression {
+                Compression::None => CompressionConfig::None,
+                Compression::GZip => CompressionConfig::GZip,
+            },
+            inner_cipher_config: match self.inner_cipher {
+                InnerCipher::ChaCha20 => InnerCipherConfig::ChaCha20,
+                InnerCipher::Salsa20 => InnerCipherConfig::Salsa20,
+                InnerCipher::Plain => InnerCipherConfig::Plain,
+            },
+            kdf_config,
+            public_custom_data: base.public_custom_data.clone(),
+        })
+    }
+}
+
+/// Cheap settings that keep tests from spending seconds in the KDF
+#[cfg(test)]
+pub(crate) fn fast_config() -> VaultConfig {
+    VaultConfig {
+        kdf: KdfAlgorithm::AesKdf,
+        kdf_iterations: 1000,
+        argon2_memory: 0,
+        argon2_parallelism: 0,
+        ..VaultConfig::default()
+    }
+}
+
+impl From<&DatabaseConfig> for VaultConfig {
+    fn from(config: &DatabaseConfig) -> Self {
+        let (kdf, kdf_iterations, argon2_memory, argon2_parallelism) = match &config.kdf_config {
+            KdfConfig::Aes { rounds } => (KdfAlgorithm::AesKdf, *rounds, 0, 0),
+            KdfConfig::Argon2 {
+                iterations,
+                memory,
+                parallelism,
+                ..
+            } => (
+                KdfAlgorithm::Argon2d,
+                *iterations,
+                memory / 1024,
+                *parallelism,
+            ),
+            KdfConfig::Argon2id {
+                iterations,
+                memory,
+                parallelism,
+                ..
+            } => (
+                KdfAlgorithm::Argon2id,
+                *iterations,
+                memory / 1024,
+                *parallelism,
+            ),
+        };
+
+        Self {
+            kdf,
+            kdf_iterations,
+            argon2_memory,
+            argon2_parallelism,
+            cipher: match config.outer_cipher_config {
+                OuterCipherConfig::AES256 => OuterCipher::Aes256,
+                OuterCipherConfig::ChaCha20 => OuterCipher::ChaCha20,
+                OuterCipherConfig::Twofish => OuterCipher::Twofish,
+            },
+            inner_cipher: match config.inner_cipher_config {
+                InnerCipherConfig::ChaCha20 => InnerCipher::ChaCha20,
+                InnerCipherConfig::Salsa20 => InnerCipher::Salsa20,
+                InnerCipherConfig::Plain => InnerCipher::Plain,
+            },
+            compression: match config.compression_config {
+                CompressionConfig::None => Compression::None,
+                CompressionConfig::GZip => Compression::GZip,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip() {
+        let config = VaultConfig {
+            kdf: KdfAlgorithm::Argon2d,
+            kdf_iterations: 3,
+            argon2_memory: 32 * 1024,
+            argon2_parallelism: 4,
+            cipher: OuterCipher::ChaCha20,
+            inner_cipher: InnerCipher::Salsa20,
+            compression: Compression::None,
+        };
+
+        let db_config = config
+            .to_database_config(&DatabaseConfig::default())
+            .unwrap();
+        assert_eq!(VaultConfig::from(&db_config), config);
+    }
+
+    #[test]
+    fn test_invalid_argon2_memory() {
+        let config = VaultConfig {
+            argon2_memory: 8,
+            argon2_parallelism: 2,
+            ..VaultConfig::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = VaultConfig {
+            argon2_memory: u64::MAX,
+            ..VaultConfig::default()
+        };
+        assert!(config.validate().is_err());
+
+        // AES-KDF ignores the Argon2 parameters
+        let config = VaultConfig {
+            kdf: KdfAlgorithm::AesKdf,
+            kdf_iterations: 60_000,
+            argon2_memory: 0,
+            argon2_parallelism: 0,
+            ..VaultConfig::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+}
@@ -1,6 +1,9 @@
 // Crypto utilities module
 // This module will contain encryption/decryption helpers
 
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use sha2::{Digest, Sha256};
+
 /// Placeholder for crypto utilities
 pub struct CryptoUtils;
 
@@ -10,4 +13,16 @@ impl CryptoUtils {
         // TODO: Implement password generation
         "placeholder".to_string()
     }
+
+    /// Fill a buffer of `len` bytes from the OS random number generator
+    pub fn random_bytes(len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        OsRng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    /// Compute the SHA-256 digest of `data`
+    pub fn sha256(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
 }
@@ -0,0 +1,139 @@
+// Deleted objects module
+//
+// KDBX keeps the UUIDs of permanently deleted entries and groups, with their
+// deletion time, in `DeletedObjects`. Merging uses the list to tell deleted
+// items from items the other copy of the vault has not seen yet.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use keepass::db::{DeletedObject as KpDeletedObject, DeletedObjects, Group as KpGroup, Node};
+use keepass::Database;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Permanently deleted entry or group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletedObject {
+    /// ID of the deleted entry or group
+    pub id: String,
+
+    /// When it was deleted
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl From<&KpDeletedObject> for DeletedObject {
+    fn from(object: &KpDeletedObject) -> Self {
+        Self {
+            id: object.uuid.to_string(),
+            deleted_at: DateTime::<Utc>::from_naive_utc_and_offset(object.deletion_time, Utc),
+        }
+    }
+}
+
+/// Record a permanently deleted node, and everything in it
+pub(crate) fn record(deleted_objects: &mut DeletedObjects, node: &Node, time: NaiveDateTime) {
+    let mut uuids = Vec::new();
+    match node {
+        Node::Entry(e) => uuids.push(e.uuid),
+        Node::Group(g) => collect_uuids(g, &mut uuids),
+    }
+
+    // Replace earlier records of the same items
+    let recorded: HashSet<Uuid> = uuids.iter().copied().collect();
+    deleted_objects
+        .objects
+        .retain(|object| !recorded.contains(&object.uuid));
+    deleted_objects
+        .objects
+        .extend(uuids.into_iter().map(|uuid| KpDeletedObject {
+            uuid,
+            deletion_time: time,
+        }));
+}
+
+/// Shrink the list, returning the number of removed records
+///
+/// Drops records of items that exist again, duplicate records (keeping the
+/// latest deletion) and, if `cutoff` is set, records of deletions before it.
+/// Other copies of the vault that still have an item deleted before the
+/// cutoff will bring it back when merged.
+pub(crate) fn compact(database: &mut Database, cutoff: Option<NaiveDateTime>) -> usize {
+    let mut existing = HashSet::new();
+    collect_uuids(&database.root, &mut existing);
+
+    let objects = &mut database.deleted_objects.objects;
+    let before = objects.len();
+    objects.sort_by_key(|object| std::cmp::Reverse(object.deletion_time));
+    let mut seen = HashSet::new();
+    objects.retain(|object| {
+        !existing.contains(&object.uuid)
+            && cutoff.is_none_or(|cutoff| object.deletion_time >= cutoff)
+            && seen.insert(object.uuid)
+    });
+    objects.reverse();
+    before - objects.len()
+}
+
+fn collect_uuids(group: &KpGroup, uuids: &mut impl Extend<Uuid>) {
+    uuids.extend([group.uuid]);
+    for child in &group.children {
+        match child {
+            Node::Entry(e) => uuids.extend([e.uuid]),
+            Node::Group(g) => collect_uuids(g, uuids),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepass::db::{Entry as KpEntry, Times};
+
+    #[test]
+    fn test_record_group_contents() {
+        let mut group = KpGroup::new("Deleted");
+        group.add_child(KpEntry::new());
+        group.add_child(KpGroup::new("Child"));
+        let mut deleted_objects = DeletedObjects::default();
+
+        let node = Node::Group(group);
+        record(&mut deleted_objects, &node, Times::now());
+        assert_eq!(deleted_objects.objects.len(), 3);
+
+        // Recording again replaces the earlier records
+        record(&mut deleted_objects, &node, Times::now());
+        assert_eq!(deleted_objects.objects.len(), 3);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut database = Database::new(Default::default());
+        let entry = KpEntry::new();
+        let restored = entry.uuid;
+        database.root.add_child(entry);
+
+        let now = Times::now();
+        let old = now - chrono::Duration::days(400);
+        let gone = Uuid::new_v4();
+        let ancient = Uuid::new_v4();
+        for (uuid, deletion_time) in [(gone, old), (gone, now), (restored, now), (ancient, old)] {
+            database.deleted_objects.objects.push(KpDeletedObject {
+                uuid,
+                deletion_time,
+            });
+        }
+
+        assert_eq!(compact(&mut database, None), 2);
+        assert_eq!(database.deleted_objects.objects.len(), 2);
+        assert_eq!(
+            compact(&mut database, Some(now - chrono::Duration::days(30))),
+            1
+        );
+        assert_eq!(
+            database.deleted_objects.objects,
+            [KpDeletedObject {
+                uuid: gone,
+                deletion_time: now,
+            }]
+        );
+    }
+}
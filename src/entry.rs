@@ -58,13 +58,23 @@ pub struct Entry {
     #[zeroize(skip)]
     pub accessed_at: DateTime<Utc>,
 
-    /// Expiry time (if any)
+    /// Number of times the entry was used
+    #[zeroize(skip)]
+    pub usage_count: u64,
+
+    /// Expiry time (if any), stored with whole-second precision
     #[zeroize(skip)]
     pub expires_at: Option<DateTime<Utc>>,
 
     /// Whether this entry is a favorite
+    ///
+    /// Stored as the `KeedaVault.Favorite` CustomData item.
     #[zeroize(skip)]
     pub is_favorite: bool,
+
+    /// Custom icon ID (see `Vault::get_custom_icons`)
+    #[zeroize(skip)]
+    pub custom_icon_id: Option<String>,
 }
 
 /// Custom field in an entry
@@ -99,8 +109,10 @@ impl Entry {
             created_at: now,
             modified_at: now,
             accessed_at: now,
+            usage_count: 0,
             expires_at: None,
             is_favorite: false,
+            custom_icon_id: None,
         }
     }
 
@@ -109,9 +121,10 @@ impl Entry {
         self.modified_at = Utc::now();
     }
 
-    /// Mark as accessed
+    /// Mark as accessed and count the use
     pub fn mark_accessed(&mut self) {
         self.accessed_at = Utc::now();
+        self.usage_count += 1;
     }
 
     /// Check if the entry has expired
@@ -12,18 +12,39 @@ pub enum VaultError {
     #[error("Failed to save vault: {0}")]
     SaveError(String),
 
+    #[error("Vault file was modified externally: {0}")]
+    ExternalModification(String),
+
+    #[error("Vault is in use by {0}")]
+    VaultInUse(String),
+
     #[error("Invalid password")]
     InvalidPassword,
 
+    #[error("Invalid key file: {0}")]
+    InvalidKeyFile(String),
+
     #[error("Vault is locked")]
     VaultLocked,
 
+    #[error("Quick unlock is not available, a full unlock is required")]
+    QuickUnlockUnavailable,
+
     #[error("Entry not found: {0}")]
     EntryNotFound(String),
 
     #[error("Group not found: {0}")]
     GroupNotFound(String),
 
+    #[error("Custom icon not found: {0}")]
+    IconNotFound(String),
+
+    #[error("Attachment not found: {0}")]
+    AttachmentNotFound(String),
+
+    #[error("Invalid move: {0}")]
+    InvalidMove(String),
+
     #[error("Invalid entry data: {0}")]
     InvalidEntry(String),
 
@@ -33,6 +54,9 @@ pub enum VaultError {
     #[error("Decryption error: {0}")]
     DecryptionError(String),
 
+    #[error("Invalid vault config: {0}")]
+    InvalidConfig(String),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -49,7 +73,12 @@ pub enum VaultError {
 // Convert keepass errors to VaultError
 impl From<keepass::error::DatabaseOpenError> for VaultError {
     fn from(err: keepass::error::DatabaseOpenError) -> Self {
-        VaultError::KeePassError(err.to_string())
+        match err {
+            keepass::error::DatabaseOpenError::Key(
+                keepass::error::DatabaseKeyError::IncorrectKey,
+            ) => VaultError::InvalidPassword,
+            _ => VaultError::KeePassError(err.to_string()),
+        }
     }
 }
 
@@ -16,6 +16,9 @@ pub struct Group {
     /// Icon identifier
     pub icon_id: u32,
 
+    /// Custom icon ID, shown instead of `icon_id` (see `Vault::get_custom_icons`)
+    pub custom_icon_id: Option<String>,
+
     /// Notes
     pub notes: String,
 
@@ -34,6 +37,7 @@ impl Group {
             parent_id,
             name,
             icon_id: 48, // Default folder icon
+            custom_icon_id: None,
             notes: String::new(),
             is_recycle_bin: false,
             is_expanded: true,
@@ -47,6 +51,7 @@ impl Group {
             parent_id: None,
             name: "Recycle Bin".to_string(),
             icon_id: 43, // Trash icon
+            custom_icon_id: None,
             notes: String::new(),
             is_recycle_bin: true,
             is_expanded: false,
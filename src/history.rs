@@ -0,0 +1,169 @@
+// Entry history module
+//
+// Previous versions of an entry are kept in its KDBX `History` element,
+// oldest version first as KeePass writes them. The number and total size of
+// versions are limited by `Meta/HistoryMaxItems` and `Meta/HistoryMaxSize`.
+
+use crate::attachment;
+use keepass::db::{Entry as KpEntry, HeaderAttachment, History, Meta, Value};
+use std::collections::HashMap;
+
+/// Versions kept per entry when the vault does not set `HistoryMaxItems`
+pub const DEFAULT_HISTORY_MAX_ITEMS: usize = 10;
+
+/// Bytes of history kept per entry when the vault does not set `HistoryMaxSize`
+pub const DEFAULT_HISTORY_MAX_SIZE: usize = 6 * 1024 * 1024;
+
+/// Build a history from versions ordered oldest first
+pub(crate) fn from_versions(versions: Vec<KpEntry>) -> History {
+    // add_entry puts each version in front, so add the newest first
+    let mut history = History::default();
+    for version in versions.into_iter().rev() {
+        history.add_entry(version);
+    }
+    history
+}
+
+/// Previous versions of an entry, oldest first
+pub(crate) fn versions(entry: &KpEntry) -> Vec<KpEntry> {
+    entry
+        .history
+        .as_ref()
+        .map(|h| h.get_entries().clone())
+        .unwrap_or_default()
+}
+
+/// Replace the history of an entry, dropping it when there are no versions
+pub(crate) fn set_versions(entry: &mut KpEntry, versions: Vec<KpEntry>) {
+    entry.history = (!versions.is_empty()).then(|| from_versions(versions));
+}
+
+/// Add `previous` as the newest version and apply the retention limits
+pub(crate) fn push(
+    entry: &mut KpEntry,
+    mut previous: KpEntry,
+    meta: &Meta,
+    binaries: &[HeaderAttachment],
+) {
+    previous.history = None;
+    let mut versions = versions(entry);
+    versions.push(previous);
+    set_versions(entry, limit(versions, meta, binaries));
+}
+
+/// Drop the oldest versions beyond the item and size limits
+///
+/// Attachments count towards the size of each version using them.
+pub(crate) fn limit(
+    mut versions: Vec<KpEntry>,
+    meta: &Meta,
+    binaries: &[HeaderAttachment],
+) -> Vec<KpEntry> {
+    let max_items = meta.history_max_items.unwrap_or(DEFAULT_HISTORY_MAX_ITEMS);
+    let max_size = meta.history_max_size.unwrap_or(DEFAULT_HISTORY_MAX_SIZE);
+
+    if versions.len() > max_items {
+        versions.drain(..versions.len() - max_items);
+    }
+
+    let sizes = attachment::sizes(binaries);
+    let mut size: usize = versions.iter().map(|v| estimated_size(v, &sizes)).sum();
+    while size > max_size && !versions.is_empty() {
+        size -= estimated_size(&versions.remove(0), &sizes);
+    }
+
+    versions
+}
+
+/// Approximate serialized size of a version, used for `HistoryMaxSize`
+fn estimated_size(entry: &KpEntry, attachment_sizes: &HashMap<String, usize>) -> usize {
+    let fields: usize = entry
+        .fields
+        .iter()
+        .map(|(key, value)| key.len() + value_size(value))
+        .sum();
+    let custom_data: usize = entry
+        .custom_data
+        .items
+        .iter()
+        .map(|(key, item)| key.len() + item.value.as_ref().map_or(0, value_size))
+        .sum();
+    let tags: usize = entry.tags.iter().map(|t| t.len() + 1).sum();
+
+    fields + custom_data + tags + attachment::total_size(entry, attachment_sizes)
+}
+
+fn value_size(value: &Value) -> usize {
+    match value {
+        Value::Bytes(b) => b.len(),
+        Value::Unprotected(s) => s.len(),
+        Value::Protected(s) => s.unsecure().len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(title: &str) -> KpEntry {
+        let mut entry = KpEntry::new();
+        entry
+            .fields
+            .insert("Title".to_string(), Value::Unprotected(title.to_string()));
+        entry
+    }
+
+    fn titles(entry: &KpEntry) -> Vec<String> {
+        versions(entry)
+            .iter()
+            .map(|v| v.get_title().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_push_keeps_oldest_first() {
+        let meta = Meta::default();
+        let mut entry = version("v3");
+        push(&mut entry, version("v1"), &meta, &[]);
+        push(&mut entry, version("v2"), &meta, &[]);
+        assert_eq!(titles(&entry), vec!["v1", "v2"]);
+    }
+
+    #[test]
+    fn test_retention_limits() {
+        let mut meta = Meta {
+            history_max_items: Some(2),
+            ..Meta::default()
+        };
+        let mut entry = version("current");
+        for title in ["v1", "v2", "v3"] {
+            push(&mut entry, version(title), &meta, &[]);
+        }
+        assert_eq!(titles(&entry), vec!["v2", "v3"]);
+
+        // Each version is about 7 bytes, so only the newest fits
+        meta.history_max_size = Some(10);
+        push(&mut entry, version("v4"), &meta, &[]);
+        assert_eq!(titles(&entry), vec!["v4"]);
+
+        meta.history_max_items = Some(0);
+        push(&mut entry, version("v5"), &meta, &[]);
+        assert!(entry.history.is_none());
+    }
+
+    #[test]
+    fn test_attachments_count_towards_size() {
+        let meta = Meta {
+            history_max_size: Some(1024),
+            ..Meta::default()
+        };
+        let mut binaries = Vec::new();
+        let mut large = version("v1");
+        attachment::attach(&mut binaries, &mut large, "a.bin", vec![0; 2048], false);
+
+        let mut entry = version("current");
+        push(&mut entry, large, &meta, &binaries);
+        push(&mut entry, version("v2"), &meta, &binaries);
+        assert_eq!(titles(&entry), vec!["v2"]);
+    }
+}
@@ -0,0 +1,149 @@
+// Custom icon module
+//
+// Custom icons live in the KDBX `Meta/CustomIcons` list as image blobs
+// (usually PNG) keyed by UUID. Entries and groups reference them through
+// `CustomIconUUID`.
+
+use keepass::db::{Group as KpGroup, Icon, Node};
+use keepass::Database;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Custom icon stored in the vault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomIcon {
+    /// Unique identifier, referenced by `Entry::custom_icon_id` and
+    /// `Group::custom_icon_id`
+    pub id: String,
+
+    /// Image data
+    pub data: Vec<u8>,
+}
+
+impl From<&Icon> for CustomIcon {
+    fn from(icon: &Icon) -> Self {
+        Self {
+            id: icon.uuid.to_string(),
+            data: icon.data.clone(),
+        }
+    }
+}
+
+/// Find an icon by ID in the database
+pub(crate) fn find_icon(database: &Database, id: &str) -> Option<Uuid> {
+    let uuid = Uuid::parse_str(id).ok()?;
+    database
+        .meta
+        .custom_icons
+        .icons
+        .iter()
+        .any(|icon| icon.uuid == uuid)
+        .then_some(uuid)
+}
+
+/// Remove an icon and clear references to it from entries and groups
+///
+/// Returns `false` if the icon does not exist.
+pub(crate) fn remove_icon(database: &mut Database, uuid: Uuid) -> bool {
+    let icons = &mut database.meta.custom_icons.icons;
+    let before = icons.len();
+    icons.retain(|icon| icon.uuid != uuid);
+    if icons.len() == before {
+        return false;
+    }
+
+    clear_references(&mut database.root, uuid);
+    true
+}
+
+fn clear_references(group: &mut KpGroup, uuid: Uuid) {
+    if group.custom_icon_uuid == Some(uuid) {
+        group.custom_icon_uuid = None;
+    }
+
+    for child in &mut group.children {
+        match child {
+            Node::Group(child_group) => clear_references(child_group, uuid),
+            Node::Entry(entry) if entry.custom_icon_uuid == Some(uuid) => {
+                entry.custom_icon_uuid = None;
+            }
+            Node::Entry(_) => {}
+        }
+    }
+}
+
+/// Remove icons no entry, history item or group refers to
+///
+/// Returns the number of removed icons.
+pub(crate) fn remove_unused(database: &mut Database) -> usize {
+    let mut used = HashSet::new();
+    collect_references(&database.root, &mut used);
+
+    let icons = &mut database.meta.custom_icons.icons;
+    let before = icons.len();
+    icons.retain(|icon| used.contains(&icon.uuid));
+    before - icons.len()
+}
+
+fn collect_references(group: &KpGroup, used: &mut HashSet<Uuid>) {
+    used.extend(group.custom_icon_uuid);
+
+    for child in &group.children {
+        match child {
+            Node::Group(child_group) => collect_references(child_group, used),
+            Node::Entry(entry) => {
+                used.extend(entry.custom_icon_uuid);
+                if let Some(history) = &entry.history {
+                    used.extend(
+                        history
+                            .get_entries()
+                            .iter()
+                            .filter_map(|e| e.custom_icon_uuid),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepass::db::Entry as KpEntry;
+
+    fn icon(database: &mut Database) -> Uuid {
+        let uuid = Uuid::new_v4();
+        database.meta.custom_icons.icons.push(Icon {
+            uuid,
+            data: vec![0x89, b'P', b'N', b'G'],
+        });
+        uuid
+    }
+
+    #[test]
+    fn test_remove_unused_icons() {
+        let mut database = Database::new(Default::default());
+        let unused = icon(&mut database);
+        let on_entry = icon(&mut database);
+        let on_group = icon(&mut database);
+
+        let mut entry = KpEntry::new();
+        entry.custom_icon_uuid = Some(on_entry);
+        let mut group = KpGroup::new("Sites");
+        group.custom_icon_uuid = Some(on_group);
+        group.add_child(entry);
+        database.root.add_child(group);
+
+        assert_eq!(remove_unused(&mut database), 1);
+        assert!(find_icon(&database, &unused.to_string()).is_none());
+        assert!(find_icon(&database, &on_entry.to_string()).is_some());
+        assert!(find_icon(&database, &on_group.to_string()).is_some());
+
+        assert!(remove_icon(&mut database, on_group));
+        assert!(!remove_icon(&mut database, on_group));
+        let Node::Group(group) = &database.root.children[0] else {
+            panic!("expected group");
+        };
+        assert_eq!(group.custom_icon_uuid, None);
+    }
+}
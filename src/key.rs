@@ -0,0 +1,377 @@
+// Composite key module
+//
+// Holds the credentials used to derive the database master key.
+
+use crate::crypto::CryptoUtils;
+use crate::error::{Result, VaultError};
+use base64::Engine;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Credentials protecting a vault (KeePass composite key)
+///
+/// A key consists of an optional master password and an optional key file.
+/// Supported key files are KeePass XML (v1.0 and v2.0), raw 32-byte keys,
+/// 64-character hex keys and arbitrary files (hashed with SHA-256).
+///
+/// # Security
+///
+/// All components are zeroized on drop and never printed by `Debug`.
+#[derive(Clone, Default, Zeroize, ZeroizeOnDrop)]
+pub struct CompositeKey {
+    password: Option<String>,
+    key_file: Option<Vec<u8>>,
+}
+
+impl CompositeKey {
+    /// Create an empty composite key
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a composite key from a master password
+    pub fn from_password(password: &str) -> Self {
+        Self::new().with_password(password)
+    }
+
+    /// Set the master password
+    pub fn with_password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Add a key file read from disk
+    pub fn with_key_file<P: AsRef<Path>>(self, path: P) -> Result<Self> {
+        let data = Zeroizing::new(std::fs::read(path)?);
+        self.with_key_file_data(&data)
+    }
+
+    /// Add a key file from its raw contents
+    pub fn with_key_file_data(mut self, data: &[u8]) -> Result<Self> {
+        self.key_file = Some(normalize_key_file(data)?);
+        Ok(self)
+    }
+
+    /// Check whether the key has a password component
+    pub fn has_password(&self) -> bool {
+        self.password.is_some()
+    }
+
+    /// Check whether the key has a key file component
+    pub fn has_key_file(&self) -> bool {
+        self.key_file.is_some()
+    }
+
+    /// Check whether the key has no components
+    pub fn is_empty(&self) -> bool {
+        self.password.is_none() && self.key_file.is_none()
+    }
+
+    /// Build the keepass key used to open or save a database
+    pub(crate) fn to_database_key(&self) -> keepass::DatabaseKey {
+        let mut key = keepass::DatabaseKey::new();
+        if let Some(password) = &self.password {
+            key = key.with_password(password);
+        }
+        if let Some(key_file) = &self.key_file {
+            // Reading from a slice cannot fail
+            key = key
+                .with_keyfile(&mut key_file.as_slice())
+                .expect("read key file from memory");
+        }
+        key
+    }
+
+    /// KDBX key elements: the SHA-256 of the password followed by the key file key
+    ///
+    /// They open the vault like the credentials do, but the password cannot
+    /// be read back from them (see `quick_unlock`).
+    pub(crate) fn key_elements(&self) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::new());
+        if let Some(password) = &self.password {
+            out.extend_from_slice(&CryptoUtils::sha256(password.as_bytes()));
+        }
+        if let Some(key_file) = &self.key_file {
+            out.extend_from_slice(&key_file_element(key_file));
+        }
+        out
+    }
+
+    /// Build a key that opens the same vaults as the key with these `key_elements`
+    pub(crate) fn from_key_elements(elements: &[u8]) -> Self {
+        // keepass uses the data of a 2.0 XML key file as a key element as is,
+        // so one such file stands in for all elements
+        let hex = Zeroizing::new(to_hex(elements));
+        let xml = Zeroizing::new(format!(
+            "<KeyFile><Meta><Version>2.0</Version></Meta><Key><Data>{}</Data></Key></KeyFile>",
+            hex.as_str()
+        ));
+        Self {
+            password: None,
+            key_file: Some(xml.as_bytes().to_vec()),
+        }
+    }
+}
+
+/// Keys are equal if they open the same vaults
+impl PartialEq for CompositeKey {
+    fn eq(&self, other: &Self) -> bool {
+        // Compare digests without exiting early, so the time taken doesn't
+        // tell how much of a guessed key matched
+        let ours = Zeroizing::new(CryptoUtils::sha256(&self.key_elements()));
+        let theirs = Zeroizing::new(CryptoUtils::sha256(&other.key_elements()));
+        let diff = ours
+            .iter()
+            .zip(theirs.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b));
+        std::hint::black_box(diff) == 0
+    }
+}
+
+impl Eq for CompositeKey {}
+
+impl fmt::Debug for CompositeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompositeKey")
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .field("key_file", &self.key_file.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// Generate the contents of a new KeePass 2.0 XML key file
+///
+/// The file holds 32 random bytes as hex, with the hash attribute KeePass
+/// uses to detect corrupted key files.
+pub fn generate_key_file() -> Vec<u8> {
+    let key = Zeroizing::new(CryptoUtils::random_bytes(32));
+    let hex = Zeroizing::new(to_hex(&key));
+    let hash = to_hex(&CryptoUtils::sha256(&key)[..4]);
+
+    // KeePass writes the key as 8 groups of 8 hex digits on two lines
+    let groups: Vec<&str> = (0..8).map(|i| &hex[i * 8..(i + 1) * 8]).collect();
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <KeyFile>\n\
+         \t<Meta>\n\
+         \t\t<Version>2.0</Version>\n\
+         \t</Meta>\n\
+         \t<Key>\n\
+         \t\t<Data Hash=\"{}\">\n\
+         \t\t\t{}\n\
+         \t\t\t{}\n\
+         \t\t</Data>\n\
+         \t</Key>\n\
+         </KeyFile>\n",
+        hash,
+        groups[..4].join(" "),
+        groups[4..].join(" ")
+    );
+
+    xml.into_bytes()
+}
+
+/// Write a new KeePass 2.0 XML key file, refusing to overwrite an existing file
+pub fn create_key_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    let data = Zeroizing::new(generate_key_file());
+
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(&data)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+/// Prepare key file contents for keepass
+///
+/// keepass handles XML and 32-byte keys itself and hashes everything else,
+/// so hex keys are decoded here and XML 2.0 hashes are verified.
+fn normalize_key_file(data: &[u8]) -> Result<Vec<u8>> {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if text.contains("<KeyFile>") {
+            verify_xml_key_hash(text)?;
+            return Ok(data.to_vec());
+        }
+
+        if data.len() == 64 {
+            if let Some(key) = from_hex(text) {
+                return Ok(key);
+            }
+        }
+    }
+
+    Ok(data.to_vec())
+}
+
+/// Verify the `Hash` attribute of a KeePass 2.0 XML key file, if present
+fn verify_xml_key_hash(xml: &str) -> Result<()> {
+    let Some(start) = xml.find("<Data") else {
+        return Err(VaultError::InvalidKeyFile(
+            "missing Data element".to_string(),
+        ));
+    };
+    let rest = &xml[start..];
+    let tag_end = rest
+        .find('>')
+        .ok_or_else(|| VaultError::InvalidKeyFile("malformed Data element".to_string()))?;
+    let tag = &rest[..tag_end];
+
+    let Some(hash_start) = tag.find("Hash=\"") else {
+        // Version 1.0 files (base64) have no hash
+        return Ok(());
+    };
+    let hash = &tag[hash_start + 6..];
+    let hash = &hash[..hash.find('"').unwrap_or(hash.len())];
+
+    let data_end = rest
+        .find("</Data>")
+        .ok_or_else(|| VaultError::InvalidKeyFile("malformed Data element".to_string()))?;
+    let hex: Zeroizing<String> = Zeroizing::new(
+        rest[tag_end + 1..data_end]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect(),
+    );
+    let key = Zeroizing::new(
+        from_hex(&hex).ok_or_else(|| VaultError::InvalidKeyFile("invalid key data".to_string()))?,
+    );
+
+    if !to_hex(&CryptoUtils::sha256(&key)[..4]).eq_ignore_ascii_case(hash) {
+        return Err(VaultError::InvalidKeyFile("hash mismatch".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Key element keepass derives from (normalized) key file contents
+///
+/// XML files contribute their decoded data, 32-byte files are used as is
+/// and anything else is hashed.
+fn key_file_element(data: &[u8]) -> Zeroizing<Vec<u8>> {
+    if let Some(key) = xml_key_data(data) {
+        return key;
+    }
+    if data.len() == 32 {
+        return Zeroizing::new(data.to_vec());
+    }
+    Zeroizing::new(CryptoUtils::sha256(data).to_vec())
+}
+
+/// Decoded key of a KeePass XML key file, if `data` is one
+fn xml_key_data(data: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+    let text = std::str::from_utf8(data).ok()?;
+    if !text.contains("<KeyFile>") {
+        return None;
+    }
+    let version = between(text, "<Version>", "</Version>");
+    let start = text.find("<Data")?;
+    let value = between(&text[start..], ">", "</Data>")?;
+
+    let key = if version == Some("2.0") {
+        let hex: Zeroizing<String> =
+            Zeroizing::new(value.chars().filter(|c| !c.is_whitespace()).collect());
+        from_hex(&hex)
+    } else {
+        base64::engine::general_purpose::STANDARD.decode(value).ok()
+    };
+    Some(Zeroizing::new(
+        key.unwrap_or_else(|| value.as_bytes().to_vec()),
+    ))
+}
+
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let from = text.find(start)? + start.len();
+    let to = from + text[from..].find(end)?;
+    Some(&text[from..to])
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_redacts_password() {
+        let key = CompositeKey::from_password("secret");
+        assert!(!format!("{:?}", key).contains("secret"));
+    }
+
+    #[test]
+    fn test_empty_key() {
+        assert!(CompositeKey::new().is_empty());
+        assert!(!CompositeKey::from_password("").is_empty());
+    }
+
+    #[test]
+    fn test_generated_key_file_verifies() {
+        let data = generate_key_file();
+        let key = CompositeKey::new().with_key_file_data(&data).unwrap();
+        assert!(key.has_key_file());
+
+        // Flipping a key digit must fail the hash check
+        let text = String::from_utf8(data).unwrap();
+        let pos = text.find("\t\t\t").unwrap() + 3;
+        let mut tampered = text.into_bytes();
+        tampered[pos] = if tampered[pos] == b'0' { b'1' } else { b'0' };
+        assert!(matches!(
+            CompositeKey::new().with_key_file_data(&tampered),
+            Err(VaultError::InvalidKeyFile(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_elements_open_the_same_database() {
+        let mut config = keepass::config::DatabaseConfig::default();
+        config.kdf_config = keepass::config::KdfConfig::Aes { rounds: 10 };
+        let v1_xml = b"<KeyFile><Meta><Version>1.00</Version></Meta><Key>\
+            <Data>ABEiM0RVZneImaq7zN3u/wARIjNEVWZ3iJmqu8zd7v8=</Data></Key></KeyFile>";
+
+        let keys = [
+            CompositeKey::from_password("master password"),
+            CompositeKey::new().with_key_file_data(&[7u8; 32]).unwrap(),
+            CompositeKey::new().with_key_file_data(b"any file").unwrap(),
+            CompositeKey::new().with_key_file_data(v1_xml).unwrap(),
+            CompositeKey::from_password("master password")
+                .with_key_file_data(&generate_key_file())
+                .unwrap(),
+        ];
+        for key in keys {
+            let mut data = Vec::new();
+            keepass::Database::new(config.clone())
+                .save(&mut data, key.to_database_key())
+                .unwrap();
+
+            let rebuilt = CompositeKey::from_key_elements(&key.key_elements());
+            assert!(rebuilt == key);
+            assert!(CompositeKey::from_password("other password") != key);
+            keepass::Database::open(&mut data.as_slice(), rebuilt.to_database_key()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_hex_key_file_is_decoded() {
+        let hex = "00112233445566778899AABBCCDDEEFF00112233445566778899AABBCCDDEEFF";
+        let key = CompositeKey::new()
+            .with_key_file_data(hex.as_bytes())
+            .unwrap();
+        assert_eq!(key.key_file.as_deref(), from_hex(hex).as_deref());
+    }
+}
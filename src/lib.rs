@@ -14,13 +14,29 @@
 // Suppress warnings from UniFFI generated code
 #![cfg_attr(feature = "uniffi", allow(unpredictable_function_pointer_comparisons))]
 
+pub mod attachment;
+pub mod auto_lock;
+pub mod config;
 pub mod crypto;
+pub mod deleted_objects;
 pub mod entry;
 pub mod error;
 pub mod group;
+pub mod history;
+pub mod icon;
+pub mod key;
+pub mod lock_file;
+pub mod merge;
+pub mod metadata;
+pub mod quick_unlock;
+pub mod recycle_bin;
+pub mod resolver;
 pub mod search;
+pub mod storage;
 pub mod totp;
 pub mod vault;
+#[cfg(feature = "webdav")]
+pub mod webdav;
 
 // UniFFI bindings (conditional compilation)
 #[cfg(feature = "uniffi")]
@@ -31,10 +47,26 @@ pub mod uniffi_bindings;
 pub use uniffi_bindings::*;
 
 // Re-export main types
+pub use attachment::Attachment;
+pub use auto_lock::{AutoLockPolicy, LockTrigger};
+pub use config::{Compression, InnerCipher, KdfAlgorithm, OuterCipher, VaultConfig};
+pub use deleted_objects::DeletedObject;
 pub use entry::{CustomField, Entry};
 pub use error::{Result, VaultError};
 pub use group::Group;
-pub use vault::{Vault, VaultConfig};
+pub use icon::CustomIcon;
+pub use key::CompositeKey;
+pub use lock_file::LockOwner;
+pub use merge::MergeReport;
+pub use metadata::VaultMetadata;
+pub use quick_unlock::QuickUnlockConfig;
+pub use recycle_bin::{RecycleBinPolicy, RecycledItem};
+pub use resolver::Resolver;
+pub use search::TagCount;
+pub use storage::{BackupPolicy, LocalFileStorage, Revision, VaultStorage};
+pub use vault::Vault;
+#[cfg(feature = "webdav")]
+pub use webdav::WebDavStorage;
 
 // UniFFI scaffolding setup
 #[cfg(feature = "uniffi")]
@@ -47,8 +79,6 @@ pub extern "C" fn test_symbol_export() -> i32 {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     #[test]
     fn it_works() {
         // Basic smoke test
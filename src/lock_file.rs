@@ -0,0 +1,245 @@
+// Lock file module
+//
+// Advisory lock files telling other KeePass clients that a database is open.
+// Uses the KeePassXC layout: `.<file name>.lock` next to the database,
+// holding the owner's pid, application name and hostname on separate lines.
+
+use crate::error::{Result, VaultError};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Process holding a database lock file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockOwner {
+    /// Process id
+    pub pid: u32,
+
+    /// Application name
+    pub application: String,
+
+    /// Host the process runs on
+    pub hostname: String,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            application: env!("CARGO_PKG_NAME").to_string(),
+            hostname: hostname(),
+        }
+    }
+
+    fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines();
+        let pid = lines.next()?.trim().parse().ok()?;
+        let application = lines.next().unwrap_or_default().trim().to_string();
+        let hostname = lines.next().unwrap_or_default().trim().to_string();
+
+        Some(Self {
+            pid,
+            application,
+            hostname,
+        })
+    }
+
+    /// Check if the owner is a dead process on this machine
+    ///
+    /// Without a hostname the machine can't be told apart, so the lock is
+    /// never stale.
+    fn is_stale(&self) -> bool {
+        !self.hostname.is_empty() && self.hostname == hostname() && !process_exists(self.pid)
+    }
+}
+
+/// Lock file held for a database, removed on drop
+#[derive(Debug)]
+pub(crate) struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    /// Create the lock file for the database at `db_path`
+    ///
+    /// Fails with `VaultError::VaultInUse` if another client holds the lock,
+    /// unless `force` is set. Lock files left behind by dead processes on
+    /// this machine are taken over. Lock files that can't be read, e.g.
+    /// because another client is still writing them, count as held.
+    pub(crate) fn acquire(db_path: &Path, force: bool) -> Result<Self> {
+        let path = lock_path(db_path);
+
+        match Self::create(&path) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                if !force {
+                    match read_owner(&path) {
+                        Some(owner) if owner.is_stale() => {}
+                        Some(owner) => {
+                            return Err(VaultError::VaultInUse(format!(
+                                "{} (pid {} on {})",
+                                owner.application, owner.pid, owner.hostname
+                            )))
+                        }
+                        None => {
+                            return Err(VaultError::VaultInUse(
+                                "unknown client (unreadable lock file)".to_string(),
+                            ))
+                        }
+                    }
+                }
+
+                fs::remove_file(&path)?;
+                Self::create(&path)?;
+            }
+            result => result?,
+        }
+
+        Ok(Self { path })
+    }
+
+    fn create(path: &Path) -> std::io::Result<()> {
+        let owner = LockOwner::current();
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        write!(
+            file,
+            "{}\n{}\n{}\n",
+            owner.pid, owner.application, owner.hostname
+        )
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        // Leave the file alone if another client took it over
+        if read_owner(&self.path) == Some(LockOwner::current()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Get the owner of the lock file for the database at `db_path`, if any
+pub fn lock_file_owner<P: AsRef<Path>>(db_path: P) -> Option<LockOwner> {
+    read_owner(&lock_path(db_path.as_ref()))
+}
+
+fn lock_path(db_path: &Path) -> PathBuf {
+    let name = db_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    db_path.with_file_name(format!(".{}.lock", name))
+}
+
+fn read_owner(path: &Path) -> Option<LockOwner> {
+    LockOwner::parse(&fs::read_to_string(path).ok()?)
+}
+
+/// Hostname as QLockFile writes it, so stale local locks are recognized
+///
+/// The environment is only a fallback: `HOSTNAME` is often unset or differs
+/// from the system name in shells and containers.
+fn hostname() -> String {
+    system_hostname()
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Hostname from `gethostname`
+#[cfg(unix)]
+fn system_hostname() -> Option<String> {
+    extern "C" {
+        fn gethostname(name: *mut std::ffi::c_char, len: usize) -> std::ffi::c_int;
+    }
+
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for `buf.len()` bytes
+    if unsafe { gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let name = String::from_utf8_lossy(&buf[..len]).into_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(not(unix))]
+fn system_hostname() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_exists(_pid: u32) -> bool {
+    // Without a portable check, assume the owner is alive
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lock_file_layout_and_release() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("vault.kdbx");
+
+        let lock = LockFile::acquire(&db_path, false).unwrap();
+        assert!(dir.path().join(".vault.kdbx.lock").exists());
+        assert_eq!(lock_file_owner(&db_path), Some(LockOwner::current()));
+
+        // A second writer is refused unless it forces the lock
+        assert!(matches!(
+            LockFile::acquire(&db_path, false),
+            Err(VaultError::VaultInUse(_))
+        ));
+
+        drop(lock);
+        assert_eq!(lock_file_owner(&db_path), None);
+    }
+
+    #[test]
+    fn test_stale_lock_file_is_taken_over() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("vault.kdbx");
+
+        // No process runs with the largest pid
+        fs::write(
+            lock_path(&db_path),
+            format!("{}\nKeePassXC\n{}\n", u32::MAX, hostname()),
+        )
+        .unwrap();
+
+        if cfg!(target_os = "linux") {
+            let _lock = LockFile::acquire(&db_path, false).unwrap();
+            assert_eq!(lock_file_owner(&db_path), Some(LockOwner::current()));
+        }
+    }
+
+    #[test]
+    fn test_unreadable_lock_file_is_held() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("vault.kdbx");
+
+        // Another client created the file but has not written it yet
+        fs::write(lock_path(&db_path), "").unwrap();
+        assert!(matches!(
+            LockFile::acquire(&db_path, false),
+            Err(VaultError::VaultInUse(_))
+        ));
+
+        let _lock = LockFile::acquire(&db_path, true).unwrap();
+        assert_eq!(lock_file_owner(&db_path), Some(LockOwner::current()));
+        assert!(!hostname().is_empty());
+
+        // The system name wins over the environment, like in QLockFile
+        #[cfg(unix)]
+        assert_eq!(Some(hostname()), system_hostname());
+    }
+}
@@ -0,0 +1,623 @@
+// Merge module
+//
+// KeePass-style synchronization of two databases. Entries and groups are
+// matched by UUID, the newer version (by LastModificationTime) wins and the
+// older entry version is kept in history. Deletions recorded in DeletedObjects
+// and moves recorded in LocationChanged are applied from both sides.
+
+use crate::attachment;
+use crate::history;
+use chrono::NaiveDateTime;
+use keepass::db::{
+    DeletedObject, Entry as KpEntry, Group as KpGroup, HeaderAttachment, Meta, Node, Times,
+};
+use keepass::Database;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Outcome of a merge, listing affected entry and group IDs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Items that only existed in the other database
+    pub added: Vec<String>,
+
+    /// Items replaced by a newer version or moved
+    pub updated: Vec<String>,
+
+    /// Items removed because the other database deleted them
+    pub deleted: Vec<String>,
+
+    /// Entries changed on both sides where the local version was kept
+    /// (the other version is preserved in the entry history)
+    pub conflicts: Vec<String>,
+}
+
+impl MergeReport {
+    /// Check if the merge changed anything
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.deleted.is_empty()
+            && self.conflicts.is_empty()
+    }
+}
+
+/// Merge `source` into `target`
+pub(crate) fn merge(target: &mut Database, source: &Database) -> MergeReport {
+    let mut report = MergeReport::default();
+    let deletions = collect_deletions(target, source);
+    let target_root = target.root.uuid;
+
+    if source.root.uuid == target_root && is_newer(&source.root.times, &target.root.times) {
+        copy_group_properties(&mut target.root, &source.root);
+        report.updated.push(target_root.to_string());
+    }
+
+    // Groups first, parents before children, so entries have somewhere to go
+    let mut groups = Vec::new();
+    collect_groups(&source.root, &mut groups);
+    for (group, parent) in groups {
+        let parent = if parent == source.root.uuid {
+            target_root
+        } else {
+            parent
+        };
+        merge_group(&mut target.root, group, parent, &deletions, &mut report);
+    }
+
+    // Binaries first, so history limits see the size of merged attachments
+    attachment::copy_missing(target, source);
+
+    let mut entries = Vec::new();
+    collect_entries(&source.root, &mut entries);
+    for (entry, parent) in entries {
+        let parent = if parent == source.root.uuid {
+            target_root
+        } else {
+            parent
+        };
+        merge_entry(
+            &mut target.root,
+            entry,
+            parent,
+            &target.meta,
+            &target.header_attachments,
+            &deletions,
+            &mut report,
+        );
+    }
+
+    apply_deletions(&mut target.root, &deletions, &mut report);
+
+    // Items changed after their deletion survive, so forget those deletions
+    target.deleted_objects.objects = deletions
+        .into_iter()
+        .filter(|(uuid, _)| !contains(&target.root, *uuid))
+        .map(|(uuid, deletion_time)| DeletedObject {
+            uuid,
+            deletion_time,
+        })
+        .collect();
+
+    // Keep the custom icons referenced by merged items
+    for icon in &source.meta.custom_icons.icons {
+        if !target
+            .meta
+            .custom_icons
+            .icons
+            .iter()
+            .any(|i| i.uuid == icon.uuid)
+        {
+            target.meta.custom_icons.icons.push(icon.clone());
+        }
+    }
+
+    report
+}
+
+fn merge_group(
+    root: &mut KpGroup,
+    group: &KpGroup,
+    parent: Uuid,
+    deletions: &HashMap<Uuid, NaiveDateTime>,
+    report: &mut MergeReport,
+) {
+    let id = group.uuid.to_string();
+    if is_deleted(deletions, group.uuid, &group.times) {
+        return;
+    }
+
+    match find_group_mut(root, group.uuid) {
+        Some(existing) => {
+            let mut changed = false;
+            if is_newer(&group.times, &existing.times) {
+                copy_group_properties(existing, group);
+                changed = true;
+            }
+            changed |= apply_move(root, group.uuid, parent, &group.times);
+            if changed {
+                report.updated.push(id);
+            }
+        }
+        None => {
+            let mut copy = group.clone();
+            copy.children.clear();
+            insert_node(root, parent, Node::Group(copy));
+            report.added.push(id);
+        }
+    }
+}
+
+fn merge_entry(
+    root: &mut KpGroup,
+    entry: &KpEntry,
+    parent: Uuid,
+    meta: &Meta,
+    binaries: &[HeaderAttachment],
+    deletions: &HashMap<Uuid, NaiveDateTime>,
+    report: &mut MergeReport,
+) {
+    let id = entry.uuid.to_string();
+    if is_deleted(deletions, entry.uuid, &entry.times) {
+        return;
+    }
+
+    let Some(existing) = find_entry_mut(root, entry.uuid) else {
+        insert_node(root, parent, Node::Entry(entry.clone()));
+        report.added.push(id);
+        return;
+    };
+
+    let mut changed = false;
+    if !same_version(existing, entry) {
+        if is_newer(&entry.times, &existing.times) {
+            let versions = merged_history(existing, entry, existing.clone(), meta, binaries);
+            *existing = entry.clone();
+            history::set_versions(existing, versions);
+            changed = true;
+        } else {
+            // An older version we already have is no conflict, just history
+            let diverged = !history::versions(existing)
+                .iter()
+                .any(|version| same_version(version, entry));
+            let versions = merged_history(existing, entry, entry.clone(), meta, binaries);
+            history::set_versions(existing, versions);
+            if diverged {
+                report.conflicts.push(id.clone());
+            }
+        }
+    }
+
+    changed |= apply_move(root, entry.uuid, parent, &entry.times);
+    if changed {
+        report.updated.push(id);
+    }
+}
+
+/// Move a node to `parent` if the other side moved it more recently
+fn apply_move(root: &mut KpGroup, uuid: Uuid, parent: Uuid, times: &Times) -> bool {
+    let Some(current_parent) = parent_of(root, uuid) else {
+        return false;
+    };
+    if current_parent == parent {
+        return false;
+    }
+
+    let Some(node) = find_node(root, uuid) else {
+        return false;
+    };
+    let newer = match (
+        times.get_location_changed(),
+        node_times(node).get_location_changed(),
+    ) {
+        (Some(theirs), Some(ours)) => theirs > ours,
+        (Some(_), None) => true,
+        _ => false,
+    };
+
+    // Never move a group into its own subtree
+    let into_self = matches!(node, Node::Group(g) if contains(g, parent));
+    if !newer || into_self || !contains(root, parent) {
+        return false;
+    }
+
+    let mut node = take_node(root, uuid).expect("node has a parent");
+    if let Some(time) = times.get_location_changed() {
+        node_times_mut(&mut node).set_location_changed(*time);
+    }
+    insert_node(root, parent, node);
+    true
+}
+
+/// Remove items deleted after their last modification
+///
+/// Groups are only removed once all their children are gone, so changes
+/// made after a group was deleted elsewhere are not lost.
+fn apply_deletions(
+    group: &mut KpGroup,
+    deletions: &HashMap<Uuid, NaiveDateTime>,
+    report: &mut MergeReport,
+) {
+    for child in group.children.iter_mut() {
+        if let Node::Group(g) = child {
+            apply_deletions(g, deletions, report);
+        }
+    }
+
+    group.children.retain(|child| {
+        let (uuid, times, empty) = match child {
+            Node::Entry(e) => (e.uuid, &e.times, true),
+            Node::Group(g) => (g.uuid, &g.times, g.children.is_empty()),
+        };
+
+        if empty && is_deleted(deletions, uuid, times) {
+            report.deleted.push(uuid.to_string());
+            false
+        } else {
+            true
+        }
+    });
+}
+
+/// Union of both DeletedObjects lists, keeping the latest deletion time
+fn collect_deletions(target: &Database, source: &Database) -> HashMap<Uuid, NaiveDateTime> {
+    let mut deletions = HashMap::new();
+    for object in target
+        .deleted_objects
+        .objects
+        .iter()
+        .chain(&source.deleted_objects.objects)
+    {
+        deletions
+            .entry(object.uuid)
+            .and_modify(|time: &mut NaiveDateTime| *time = (*time).max(object.deletion_time))
+            .or_insert(object.deletion_time);
+    }
+    deletions
+}
+
+fn is_deleted(deletions: &HashMap<Uuid, NaiveDateTime>, uuid: Uuid, times: &Times) -> bool {
+    deletions
+        .get(&uuid)
+        .is_some_and(|deleted| last_modification(times) <= *deleted)
+}
+
+fn is_newer(theirs: &Times, ours: &Times) -> bool {
+    last_modification(theirs) > last_modification(ours)
+}
+
+fn last_modification(times: &Times) -> NaiveDateTime {
+    times
+        .get_last_modification()
+        .copied()
+        .unwrap_or_else(Times::epoch)
+}
+
+/// Compare two entry versions, ignoring history and access statistics
+fn same_version(a: &KpEntry, b: &KpEntry) -> bool {
+    last_modification(&a.times) == last_modification(&b.times)
+        && a.fields == b.fields
+        && a.tags == b.tags
+        && a.icon_id == b.icon_id
+        && a.custom_icon_uuid == b.custom_icon_uuid
+        && a.custom_data == b.custom_data
+}
+
+/// Combine the history of both versions plus `extra`, oldest version first
+///
+/// The combined history is cut down to the vault's retention limits.
+fn merged_history(
+    a: &KpEntry,
+    b: &KpEntry,
+    mut extra: KpEntry,
+    meta: &Meta,
+    binaries: &[HeaderAttachment],
+) -> Vec<KpEntry> {
+    extra.history = None;
+
+    let mut versions: Vec<KpEntry> = Vec::new();
+    let older = a
+        .history
+        .iter()
+        .chain(&b.history)
+        .flat_map(|h| h.get_entries().iter().cloned())
+        .chain(std::iter::once(extra));
+    for version in older {
+        if !versions.iter().any(|v| same_version(v, &version)) {
+            versions.push(version);
+        }
+    }
+    versions.sort_by_key(|v| last_modification(&v.times));
+
+    history::limit(versions, meta, binaries)
+}
+
+fn copy_group_properties(target: &mut KpGroup, source: &KpGroup) {
+    target.name = source.name.clone();
+    target.notes = source.notes.clone();
+    target.icon_id = source.icon_id;
+    target.custom_icon_uuid = source.custom_icon_uuid;
+    target.times = source.times.clone();
+    target.custom_data = source.custom_data.clone();
+    target.is_expanded = source.is_expanded;
+}
+
+// Tree helpers
+
+/// Collect all groups below `group` with their parent, parents first
+fn collect_groups<'a>(group: &'a KpGroup, out: &mut Vec<(&'a KpGroup, Uuid)>) {
+    for child in &group.children {
+        if let Node::Group(g) = child {
+            out.push((g, group.uuid));
+            collect_groups(g, out);
+        }
+    }
+}
+
+fn collect_entries<'a>(group: &'a KpGroup, out: &mut Vec<(&'a KpEntry, Uuid)>) {
+    for child in &group.children {
+        match child {
+            Node::Entry(e) => out.push((e, group.uuid)),
+            Node::Group(g) => collect_entries(g, out),
+        }
+    }
+}
+
+fn find_node(group: &KpGroup, uuid: Uuid) -> Option<&Node> {
+    for child in &group.children {
+        match child {
+            Node::Entry(e) if e.uuid == uuid => return Some(child),
+            Node::Group(g) if g.uuid == uuid => return Some(child),
+            Node::Group(g) => {
+                if let Some(found) = find_node(g, uuid) {
+                    return Some(found);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn find_group_mut(group: &mut KpGroup, uuid: Uuid) -> Option<&mut KpGroup> {
+    if group.uuid == uuid {
+        return Some(group);
+    }
+    for child in group.children.iter_mut() {
+        if let Node::Group(g) = child {
+            if let Some(found) = find_group_mut(g, uuid) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+fn find_entry_mut(group: &mut KpGroup, uuid: Uuid) -> Option<&mut KpEntry> {
+    for child in group.children.iter_mut() {
+        match child {
+            Node::Entry(e) if e.uuid == uuid => return Some(e),
+            Node::Group(g) => {
+                if let Some(found) = find_entry_mut(g, uuid) {
+                    return Some(found);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parent_of(group: &KpGroup, uuid: Uuid) -> Option<Uuid> {
+    for child in &group.children {
+        match child {
+            Node::Entry(e) if e.uuid == uuid => return Some(group.uuid),
+            Node::Group(g) if g.uuid == uuid => return Some(group.uuid),
+            Node::Group(g) => {
+                if let Some(found) = parent_of(g, uuid) {
+                    return Some(found);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn contains(group: &KpGroup, uuid: Uuid) -> bool {
+    group.uuid == uuid || find_node(group, uuid).is_some()
+}
+
+fn take_node(group: &mut KpGroup, uuid: Uuid) -> Option<Node> {
+    let node_uuid = |node: &Node| match node {
+        Node::Entry(e) => e.uuid,
+        Node::Group(g) => g.uuid,
+    };
+
+    if let Some(pos) = group.children.iter().position(|c| node_uuid(c) == uuid) {
+        return Some(group.children.remove(pos));
+    }
+    group.children.iter_mut().find_map(|child| match child {
+        Node::Group(g) => take_node(g, uuid),
+        Node::Entry(_) => None,
+    })
+}
+
+/// Insert a node under `parent`, falling back to the root group
+fn insert_node(root: &mut KpGroup, parent: Uuid, node: Node) {
+    match find_group_mut(root, parent) {
+        Some(group) => group.children.push(node),
+        None => root.children.push(node),
+    }
+}
+
+fn node_times(node: &Node) -> &Times {
+    match node {
+        Node::Entry(e) => &e.times,
+        Node::Group(g) => &g.times,
+    }
+}
+
+fn node_times_mut(node: &mut Node) -> &mut Times {
+    match node {
+        Node::Entry(e) => &mut e.times,
+        Node::Group(g) => &mut g.times,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepass::config::DatabaseConfig;
+    use keepass::db::Value;
+
+    fn time(secs: i64) -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(secs, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn entry(title: &str, modified: i64) -> KpEntry {
+        let mut entry = KpEntry::new();
+        entry
+            .fields
+            .insert("Title".to_string(), Value::Unprotected(title.to_string()));
+        entry.times.set_last_modification(time(modified));
+        entry.times.set_location_changed(time(modified));
+        entry
+    }
+
+    fn title(entry: &KpEntry) -> &str {
+        entry.get_title().unwrap()
+    }
+
+    #[test]
+    fn test_newer_version_wins_and_older_goes_to_history() {
+        let mut ours = Database::new(DatabaseConfig::default());
+        let original = entry("Original", 100);
+        ours.root.add_child(original.clone());
+
+        let mut theirs = ours.clone();
+        let mut newer = entry("Renamed", 200);
+        newer.uuid = original.uuid;
+        theirs.root.children = vec![Node::Entry(newer)];
+        theirs.root.add_child(entry("New", 150));
+
+        let report = merge(&mut ours, &theirs);
+        assert_eq!(report.added.len(), 1);
+        assert_eq!(report.updated, vec![original.uuid.to_string()]);
+        assert!(report.conflicts.is_empty());
+
+        let merged = find_entry_mut(&mut ours.root, original.uuid).unwrap();
+        assert_eq!(title(merged), "Renamed");
+        let history = merged.history.as_ref().unwrap().get_entries();
+        assert_eq!(history.len(), 1);
+        assert_eq!(title(&history[0]), "Original");
+
+        // Merging again is a no-op
+        assert!(merge(&mut ours, &theirs).is_empty());
+    }
+
+    #[test]
+    fn test_merged_history_is_limited() {
+        let mut ours = Database::new(DatabaseConfig::default());
+        ours.meta.history_max_items = Some(2);
+        let mut local = entry("Local", 300);
+        local.history = Some(history::from_versions(vec![
+            entry("Ours 1", 100),
+            entry("Ours 2", 150),
+        ]));
+        ours.root.add_child(local.clone());
+
+        let mut remote = entry("Remote", 400);
+        remote.uuid = local.uuid;
+        remote.history = Some(history::from_versions(vec![
+            entry("Theirs 1", 120),
+            entry("Theirs 2", 200),
+        ]));
+        let mut theirs = Database::new(DatabaseConfig::default());
+        theirs.root.uuid = ours.root.uuid;
+        theirs.root.add_child(remote);
+
+        merge(&mut ours, &theirs);
+        let merged = find_entry_mut(&mut ours.root, local.uuid).unwrap();
+        assert_eq!(title(merged), "Remote");
+        let history = merged.history.as_ref().unwrap().get_entries();
+        let titles: Vec<&str> = history.iter().map(title).collect();
+        assert_eq!(titles, vec!["Theirs 2", "Local"]);
+    }
+
+    #[test]
+    fn test_local_only_change_is_no_conflict() {
+        let mut theirs = Database::new(DatabaseConfig::default());
+        let original = entry("Original", 100);
+        theirs.root.add_child(original.clone());
+
+        // We edited our copy, keeping the original in history
+        let mut ours = theirs.clone();
+        let mut edited = entry("Edited", 200);
+        edited.uuid = original.uuid;
+        edited.history = Some(history::from_versions(vec![original.clone()]));
+        ours.root.children = vec![Node::Entry(edited)];
+
+        let report = merge(&mut ours, &theirs);
+        assert!(report.conflicts.is_empty());
+        assert!(report.is_empty());
+
+        let merged = find_entry_mut(&mut ours.root, original.uuid).unwrap();
+        assert_eq!(title(merged), "Edited");
+        assert_eq!(merged.history.as_ref().unwrap().get_entries().len(), 1);
+    }
+
+    #[test]
+    fn test_local_change_wins_conflict() {
+        let mut ours = Database::new(DatabaseConfig::default());
+        let local = entry("Local", 300);
+        ours.root.add_child(local.clone());
+
+        let mut theirs = ours.clone();
+        let mut remote = entry("Remote", 200);
+        remote.uuid = local.uuid;
+        theirs.root.children = vec![Node::Entry(remote)];
+
+        let report = merge(&mut ours, &theirs);
+        assert_eq!(report.conflicts, vec![local.uuid.to_string()]);
+
+        let merged = find_entry_mut(&mut ours.root, local.uuid).unwrap();
+        assert_eq!(title(merged), "Local");
+        assert_eq!(
+            title(&merged.history.as_ref().unwrap().get_entries()[0]),
+            "Remote"
+        );
+    }
+
+    #[test]
+    fn test_deletions_and_moves() {
+        let mut ours = Database::new(DatabaseConfig::default());
+        let mut folder = KpGroup::new("Folder");
+        folder.times.set_last_modification(time(100));
+        let kept = entry("Kept", 100);
+        let removed = entry("Removed", 100);
+        ours.root.add_child(folder.clone());
+        ours.root.add_child(kept.clone());
+        ours.root.add_child(removed.clone());
+
+        // They moved one entry into the folder and deleted the other
+        let mut theirs = ours.clone();
+        let mut moved = take_node(&mut theirs.root, kept.uuid).unwrap();
+        node_times_mut(&mut moved).set_location_changed(time(200));
+        insert_node(&mut theirs.root, folder.uuid, moved);
+        take_node(&mut theirs.root, removed.uuid).unwrap();
+        theirs.deleted_objects.objects.push(DeletedObject {
+            uuid: removed.uuid,
+            deletion_time: time(200),
+        });
+
+        let report = merge(&mut ours, &theirs);
+        assert_eq!(report.updated, vec![kept.uuid.to_string()]);
+        assert_eq!(report.deleted, vec![removed.uuid.to_string()]);
+        assert_eq!(parent_of(&ours.root, kept.uuid), Some(folder.uuid));
+        assert!(!contains(&ours.root, removed.uuid));
+        assert_eq!(ours.deleted_objects.objects.len(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+// Metadata module
+//
+// Database-wide settings from KDBX `Meta`, shared with other KeePass clients.
+
+use crate::error::{Result, VaultError};
+use chrono::{DateTime, Utc};
+use keepass::db::{Color, Meta, Times};
+
+/// Database name, color and other database-wide settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VaultMetadata {
+    /// Database name shown instead of the file name
+    pub name: String,
+
+    /// Free-form description
+    pub description: String,
+
+    /// Username suggested for new entries
+    pub default_username: String,
+
+    /// Database color as `#RRGGBB`
+    pub color: Option<String>,
+
+    /// Application that created or last saved the database
+    pub generator: String,
+
+    /// Days to keep history items and deleted objects during maintenance
+    pub maintenance_history_days: Option<u32>,
+
+    /// When the master key was last changed (read-only)
+    pub master_key_changed: Option<DateTime<Utc>>,
+
+    /// Days after which users should be reminded to change the master key
+    pub master_key_change_rec: Option<u32>,
+
+    /// Days after which users must change the master key
+    pub master_key_change_force: Option<u32>,
+}
+
+impl From<&Meta> for VaultMetadata {
+    fn from(meta: &Meta) -> Self {
+        Self {
+            name: meta.database_name.clone().unwrap_or_default(),
+            description: meta.database_description.clone().unwrap_or_default(),
+            default_username: meta.default_username.clone().unwrap_or_default(),
+            color: meta
+                .color
+                .as_ref()
+                .map(|color| color.to_string().to_uppercase()),
+            generator: meta.generator.clone().unwrap_or_default(),
+            maintenance_history_days: meta
+                .maintenance_history_days
+                .and_then(|days| days.try_into().ok()),
+            master_key_changed: meta
+                .master_key_changed
+                .map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
+            // KeePass stores -1 when a reminder is turned off
+            master_key_change_rec: meta
+                .master_key_change_rec
+                .and_then(|days| days.try_into().ok()),
+            master_key_change_force: meta
+                .master_key_change_force
+                .and_then(|days| days.try_into().ok()),
+        }
+    }
+}
+
+/// Write `metadata` to `meta`, updating the change times of changed values
+///
+/// `master_key_changed` is ignored; it is updated when the credentials change.
+pub(crate) fn apply(meta: &mut Meta, metadata: &VaultMetadata) -> Result<()> {
+    let color = match &metadata.color {
+        Some(color) => Some(color.parse::<Color>().map_err(|_| {
+            VaultError::InvalidConfig(format!("Invalid database color: {}", color))
+        })?),
+        None => None,
+    };
+    let now = Times::now();
+
+    if set_text(&mut meta.database_name, &metadata.name) {
+        meta.database_name_changed = Some(now);
+    }
+    if set_text(&mut meta.database_description, &metadata.description) {
+        meta.database_description_changed = Some(now);
+    }
+    if set_text(&mut meta.default_username, &metadata.default_username) {
+        meta.default_username_changed = Some(now);
+    }
+    set_text(&mut meta.generator, &metadata.generator);
+
+    let maintenance_history_days = metadata.maintenance_history_days.map(|days| days as usize);
+    let master_key_change_rec = metadata.master_key_change_rec.map(kdbx_days);
+    let master_key_change_force = metadata.master_key_change_force.map(kdbx_days);
+    if meta.color != color
+        || meta.maintenance_history_days != maintenance_history_days
+        || meta.master_key_change_rec.filter(|days| *days >= 0) != master_key_change_rec
+        || meta.master_key_change_force.filter(|days| *days >= 0) != master_key_change_force
+    {
+        meta.color = color;
+        meta.maintenance_history_days = maintenance_history_days;
+        meta.master_key_change_rec = master_key_change_rec;
+        meta.master_key_change_force = master_key_change_force;
+        meta.settings_changed = Some(now);
+    }
+
+    Ok(())
+}
+
+fn kdbx_days(days: u32) -> isize {
+    isize::try_from(days).unwrap_or(isize::MAX)
+}
+
+/// Set an optional text field, storing empty text as missing
+///
+/// Returns true if the value changed.
+fn set_text(field: &mut Option<String>, value: &str) -> bool {
+    let value = (!value.is_empty()).then(|| value.to_string());
+    if *field == value {
+        return false;
+    }
+    *field = value;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_read_back() {
+        let mut meta = Meta::default();
+        let metadata = VaultMetadata {
+            name: "Family".to_string(),
+            color: Some("#FF8800".to_string()),
+            master_key_change_rec: Some(180),
+            ..Default::default()
+        };
+
+        apply(&mut meta, &metadata).unwrap();
+        assert!(meta.database_name_changed.is_some());
+        assert!(meta.settings_changed.is_some());
+        assert!(meta.database_description.is_none());
+        assert_eq!(VaultMetadata::from(&meta), metadata);
+
+        // Turned off reminders read as None
+        meta.master_key_change_force = Some(-1);
+        assert_eq!(VaultMetadata::from(&meta).master_key_change_force, None);
+    }
+
+    #[test]
+    fn test_invalid_color() {
+        let mut meta = Meta::default();
+        let metadata = VaultMetadata {
+            name: "Unchanged".to_string(),
+            color: Some("orange".to_string()),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            apply(&mut meta, &metadata),
+            Err(VaultError::InvalidConfig(_))
+        ));
+        assert!(meta.database_name.is_none());
+    }
+}
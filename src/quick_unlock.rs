@@ -0,0 +1,189 @@
+// Quick unlock module
+//
+// Keeps the composite key sealed under a short PIN so a locked vault can be
+// reopened without retyping the master password. Only the KDBX key elements
+// are sealed, never the password itself, so brute-forcing the PIN does not
+// reveal a password that may be reused elsewhere. The sealed key only lives
+// in memory and is destroyed after too many wrong PINs.
+
+use crate::crypto::CryptoUtils;
+use crate::error::{Result, VaultError};
+use crate::key::CompositeKey;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Quick unlock settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickUnlockConfig {
+    /// Wrong PINs allowed before the quick unlock material is destroyed
+    pub max_attempts: u32,
+
+    /// Argon2id memory for the PIN key (in KB)
+    pub argon2_memory: u32,
+
+    /// Argon2id iterations for the PIN key
+    pub argon2_iterations: u32,
+}
+
+impl Default for QuickUnlockConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            argon2_memory: 16 * 1024, // 16 MB
+            argon2_iterations: 2,
+        }
+    }
+}
+
+/// Composite key sealed under a PIN-derived key
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub(crate) struct QuickUnlock {
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+    remaining_attempts: u32,
+    #[zeroize(skip)]
+    config: QuickUnlockConfig,
+}
+
+impl QuickUnlock {
+    /// Seal `key` under `pin`
+    pub(crate) fn seal(pin: &str, key: &CompositeKey, config: QuickUnlockConfig) -> Result<Self> {
+        if pin.is_empty() {
+            return Err(VaultError::InvalidPassword);
+        }
+        if config.max_attempts == 0 {
+            return Err(VaultError::InvalidConfig(
+                "Quick unlock needs at least one attempt".to_string(),
+            ));
+        }
+
+        let salt = CryptoUtils::random_bytes(16);
+        let nonce = CryptoUtils::random_bytes(12);
+        let pin_key = Self::derive_key(pin, &salt, &config)?;
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(pin_key.as_slice()));
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), key.key_elements().as_slice())
+            .map_err(|_| {
+                VaultError::EncryptionError("Failed to seal quick unlock key".to_string())
+            })?;
+
+        Ok(Self {
+            salt,
+            nonce,
+            ciphertext,
+            remaining_attempts: config.max_attempts,
+            config,
+        })
+    }
+
+    /// Recover the composite key, consuming an attempt if the PIN is wrong
+    pub(crate) fn open(&mut self, pin: &str) -> Result<CompositeKey> {
+        if self.remaining_attempts == 0 {
+            return Err(VaultError::QuickUnlockUnavailable);
+        }
+
+        let pin_key = Self::derive_key(pin, &self.salt, &self.config)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(pin_key.as_slice()));
+
+        match cipher.decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_slice()) {
+            Ok(plaintext) => {
+                let plaintext = Zeroizing::new(plaintext);
+                self.remaining_attempts = self.config.max_attempts;
+                Ok(CompositeKey::from_key_elements(&plaintext))
+            }
+            Err(_) => {
+                self.remaining_attempts -= 1;
+                Err(VaultError::InvalidPassword)
+            }
+        }
+    }
+
+    /// Wrong PINs left before the material must be destroyed
+    pub(crate) fn remaining_attempts(&self) -> u32 {
+        self.remaining_attempts
+    }
+
+    fn derive_key(
+        pin: &str,
+        salt: &[u8],
+        config: &QuickUnlockConfig,
+    ) -> Result<Zeroizing<[u8; 32]>> {
+        let params = Params::new(config.argon2_memory, config.argon2_iterations, 1, Some(32))
+            .map_err(|e| VaultError::InvalidConfig(format!("Invalid quick unlock KDF: {}", e)))?;
+
+        let mut out = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(pin.as_bytes(), salt, out.as_mut_slice())
+            .map_err(|e| {
+                VaultError::EncryptionError(format!("PIN key derivation failed: {}", e))
+            })?;
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> QuickUnlockConfig {
+        QuickUnlockConfig {
+            max_attempts: 2,
+            argon2_memory: 64,
+            argon2_iterations: 1,
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let key = CompositeKey::from_password("master password");
+        let mut sealed = QuickUnlock::seal("1234", &key, test_config()).unwrap();
+
+        assert!(sealed.open("1234").unwrap() == key);
+    }
+
+    #[test]
+    fn test_password_is_not_sealed() {
+        let key = CompositeKey::from_password("master password")
+            .with_key_file_data(&crate::key::generate_key_file())
+            .unwrap();
+        let sealed = QuickUnlock::seal("1234", &key, test_config()).unwrap();
+
+        let pin_key = QuickUnlock::derive_key("1234", &sealed.salt, &sealed.config).unwrap();
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(pin_key.as_slice()))
+            .decrypt(
+                Nonce::from_slice(&sealed.nonce),
+                sealed.ciphertext.as_slice(),
+            )
+            .unwrap();
+        assert!(!plaintext
+            .windows(b"master password".len())
+            .any(|window| window == b"master password"));
+    }
+
+    #[test]
+    fn test_attempts_exhausted() {
+        let key = CompositeKey::from_password("master password");
+        let mut sealed = QuickUnlock::seal("1234", &key, test_config()).unwrap();
+
+        assert!(matches!(
+            sealed.open("0000"),
+            Err(VaultError::InvalidPassword)
+        ));
+        assert_eq!(sealed.remaining_attempts(), 1);
+        assert!(matches!(
+            sealed.open("0000"),
+            Err(VaultError::InvalidPassword)
+        ));
+
+        // Even the right PIN is refused once attempts run out
+        assert!(matches!(
+            sealed.open("1234"),
+            Err(VaultError::QuickUnlockUnavailable)
+        ));
+    }
+}
@@ -0,0 +1,294 @@
+// Recycle bin module
+//
+// The recycle bin is the group referenced by `Meta/RecycleBinUUID`, enabled
+// by `Meta/RecycleBinEnabled`, as in KeePass and KeePassXC. Vaults written by
+// older versions of this library have no UUID and a top-level bin named
+// "Recycle Bin" or "回收站", which is adopted the first time it is used.
+
+use crate::deleted_objects;
+use crate::group::Group;
+use crate::vault::PREVIOUS_PARENT_KEY;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use keepass::db::{CustomData, Group as KpGroup, Node, Times, Value};
+use keepass::Database;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Names of recycle bins created without `Meta/RecycleBinUUID`
+const LEGACY_NAMES: [&str; 2] = ["Recycle Bin", "回收站"];
+
+/// Automatic recycle bin purge settings
+///
+/// The default keeps everything until the bin is emptied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecycleBinPolicy {
+    /// Purge items deleted more than this many days ago
+    pub max_age_days: Option<u32>,
+
+    /// Keep at most this many items, purging the oldest deletions first
+    pub max_items: Option<usize>,
+}
+
+/// Entry or group in the recycle bin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecycledItem {
+    /// Entry or group ID
+    pub id: String,
+
+    /// Entry title or group name
+    pub name: String,
+
+    /// Whether the item is a group
+    pub is_group: bool,
+
+    /// When the item was moved to the recycle bin
+    pub deleted_at: Option<DateTime<Utc>>,
+
+    /// Group the item was deleted from
+    pub previous_parent_id: Option<String>,
+}
+
+/// Check if deleted items go to the recycle bin (enabled unless turned off)
+pub(crate) fn is_enabled(database: &Database) -> bool {
+    database.meta.recyclebin_enabled.unwrap_or(true)
+}
+
+/// Turn the recycle bin on or off
+pub(crate) fn set_enabled(database: &mut Database, enabled: bool) {
+    database.meta.recyclebin_enabled = Some(enabled);
+}
+
+/// UUID of the recycle bin group, if it exists
+pub(crate) fn uuid(database: &Database) -> Option<Uuid> {
+    find(database).map(|bin| bin.uuid)
+}
+
+/// The recycle bin group, if it exists
+pub(crate) fn find(database: &Database) -> Option<&KpGroup> {
+    match database.meta.recyclebin_uuid {
+        Some(uuid) if !uuid.is_nil() => find_group(&database.root, uuid),
+        _ => database.root.children.iter().find_map(|child| match child {
+            Node::Group(g) if LEGACY_NAMES.contains(&g.name.as_str()) => Some(g),
+            _ => None,
+        }),
+    }
+}
+
+/// The recycle bin group, created or adopted if needed
+pub(crate) fn get_or_create(database: &mut Database) -> &mut KpGroup {
+    let uuid = match find(database).map(|bin| bin.uuid) {
+        Some(uuid) if !uuid.is_nil() => uuid,
+        // Legacy bins were created without a UUID
+        Some(_) => {
+            let uuid = Uuid::new_v4();
+            let bin = find_group_mut(&mut database.root, Uuid::nil()).expect("legacy bin exists");
+            bin.uuid = uuid;
+            uuid
+        }
+        None => {
+            let model = Group::new_recycle_bin();
+            let mut bin = KpGroup::new(&model.name);
+            bin.icon_id = Some(model.icon_id as usize);
+            bin.is_expanded = model.is_expanded;
+            let uuid = bin.uuid;
+            database.root.add_child(bin);
+            uuid
+        }
+    };
+
+    if database.meta.recyclebin_uuid != Some(uuid) {
+        database.meta.recyclebin_uuid = Some(uuid);
+        database.meta.recyclebin_changed = Some(Times::now());
+    }
+
+    find_group_mut(&mut database.root, uuid).expect("recycle bin exists")
+}
+
+/// Forget the recycle bin after its group was deleted
+pub(crate) fn clear(database: &mut Database) {
+    database.meta.recyclebin_uuid = None;
+    database.meta.recyclebin_changed = Some(Times::now());
+}
+
+/// Check if an entry or group is inside the recycle bin
+pub(crate) fn contains(database: &Database, id: &str) -> bool {
+    find(database).is_some_and(|bin| contains_item(bin, id))
+}
+
+/// Items directly in the recycle bin, in the order they were deleted
+///
+/// Entries and groups inside deleted groups are not listed; they come back
+/// with their group.
+pub(crate) fn items(database: &Database) -> Vec<RecycledItem> {
+    let Some(bin) = find(database) else {
+        return Vec::new();
+    };
+
+    bin.children
+        .iter()
+        .map(|child| {
+            let (id, name, times, custom_data) = match child {
+                Node::Entry(e) => (
+                    e.uuid,
+                    e.get_title().unwrap_or_default().to_string(),
+                    &e.times,
+                    &e.custom_data,
+                ),
+                Node::Group(g) => (g.uuid, g.name.clone(), &g.times, &g.custom_data),
+            };
+            RecycledItem {
+                id: id.to_string(),
+                name,
+                is_group: matches!(child, Node::Group(_)),
+                deleted_at: times
+                    .get_location_changed()
+                    .map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(*dt, Utc)),
+                previous_parent_id: previous_parent(custom_data).map(|uuid| uuid.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Permanently delete the bin items the policy no longer keeps
+///
+/// Items without a deletion time never expire but count as the oldest ones.
+/// Purged items are recorded in `DeletedObjects`. Returns the number of
+/// purged items.
+pub(crate) fn purge(
+    database: &mut Database,
+    policy: &RecycleBinPolicy,
+    now: NaiveDateTime,
+) -> usize {
+    let Some(uuid) = uuid(database) else {
+        return 0;
+    };
+    let bin = find_group_mut(&mut database.root, uuid).expect("recycle bin exists");
+
+    let deleted_at = |node: &Node| match node {
+        Node::Entry(e) => e.times.get_location_changed().copied(),
+        Node::Group(g) => g.times.get_location_changed().copied(),
+    };
+    let cutoff = policy
+        .max_age_days
+        .map(|days| now - chrono::Duration::days(days.into()));
+    let expired =
+        |node: &Node| matches!((deleted_at(node), cutoff), (Some(at), Some(cutoff)) if at < cutoff);
+
+    let mut kept: Vec<usize> = (0..bin.children.len())
+        .filter(|&i| !expired(&bin.children[i]))
+        .collect();
+    kept.sort_by_key(|&i| deleted_at(&bin.children[i]));
+    let excess = policy
+        .max_items
+        .map_or(0, |max| kept.len().saturating_sub(max));
+    let kept: HashSet<usize> = kept.into_iter().skip(excess).collect();
+
+    let mut purged = 0;
+    for (i, node) in std::mem::take(&mut bin.children).into_iter().enumerate() {
+        if kept.contains(&i) {
+            bin.children.push(node);
+        } else {
+            deleted_objects::record(&mut database.deleted_objects, &node, now);
+            purged += 1;
+        }
+    }
+    purged
+}
+
+/// Group an entry or group was last moved out of
+pub(crate) fn previous_parent(custom_data: &CustomData) -> Option<Uuid> {
+    match &custom_data.items.get(PREVIOUS_PARENT_KEY)?.value {
+        Some(Value::Unprotected(id)) => Uuid::parse_str(id).ok(),
+        _ => None,
+    }
+}
+
+fn contains_item(group: &KpGroup, id: &str) -> bool {
+    group.children.iter().any(|child| match child {
+        Node::Entry(e) => e.uuid.to_string() == id,
+        Node::Group(g) => g.uuid.to_string() == id || contains_item(g, id),
+    })
+}
+
+fn find_group(group: &KpGroup, uuid: Uuid) -> Option<&KpGroup> {
+    if group.uuid == uuid {
+        return Some(group);
+    }
+    group.children.iter().find_map(|child| match child {
+        Node::Group(g) => find_group(g, uuid),
+        Node::Entry(_) => None,
+    })
+}
+
+fn find_group_mut(group: &mut KpGroup, uuid: Uuid) -> Option<&mut KpGroup> {
+    if group.uuid == uuid {
+        return Some(group);
+    }
+    group.children.iter_mut().find_map(|child| match child {
+        Node::Group(g) => find_group_mut(g, uuid),
+        Node::Entry(_) => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bin_is_found_by_uuid() {
+        let mut database = Database::new(Default::default());
+        assert!(find(&database).is_none());
+
+        let uuid = get_or_create(&mut database).uuid;
+        assert_eq!(database.meta.recyclebin_uuid, Some(uuid));
+        assert_eq!(find(&database).unwrap().icon_id, Some(43));
+
+        // Renamed or localized bins are still recognized
+        find_group_mut(&mut database.root, uuid).unwrap().name = "Papierkorb".to_string();
+        assert_eq!(super::uuid(&database), Some(uuid));
+        assert_eq!(get_or_create(&mut database).uuid, uuid);
+    }
+
+    #[test]
+    fn test_purge_by_age_and_count() {
+        let mut database = Database::new(Default::default());
+        let now = Times::now();
+        for (title, days_ago) in [("old", 40), ("recent", 2), ("newest", 1), ("older", 10)] {
+            let mut entry = keepass::db::Entry::new();
+            entry
+                .fields
+                .insert("Title".to_string(), Value::Unprotected(title.to_string()));
+            entry
+                .times
+                .set_location_changed(now - chrono::Duration::days(days_ago));
+            get_or_create(&mut database).add_child(entry);
+        }
+
+        // Nothing expires without a policy
+        assert_eq!(purge(&mut database, &RecycleBinPolicy::default(), now), 0);
+
+        let policy = RecycleBinPolicy {
+            max_age_days: Some(30),
+            max_items: Some(2),
+        };
+        assert_eq!(purge(&mut database, &policy, now), 2);
+        let names: Vec<String> = items(&database).into_iter().map(|i| i.name).collect();
+        assert_eq!(names, ["recent", "newest"]);
+        assert_eq!(database.deleted_objects.objects.len(), 2);
+    }
+
+    #[test]
+    fn test_legacy_bin_is_adopted() {
+        let mut database = Database::new(Default::default());
+        database.root.add_child(KpGroup {
+            name: "Recycle Bin".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(super::uuid(&database), Some(Uuid::nil()));
+
+        let uuid = get_or_create(&mut database).uuid;
+        assert!(!uuid.is_nil());
+        assert_eq!(database.meta.recyclebin_uuid, Some(uuid));
+        assert_eq!(database.root.children.len(), 1);
+    }
+}
@@ -0,0 +1,388 @@
+// Placeholder resolver module
+//
+// Expands KeePass field references (`{REF:P@I:<uuid>}`) and standard
+// placeholders (`{USERNAME}`, `{URL:HOST}`, `{DT_SIMPLE}`, ...) in entry
+// fields. Unknown placeholders, reference cycles, references nested deeper
+// than `MAX_DEPTH` and fields beyond `MAX_EXPANSIONS` are left as they are.
+
+use crate::entry::Entry;
+use chrono::{DateTime, Local, Utc};
+
+/// Maximum nesting of references and field placeholders
+pub const MAX_DEPTH: usize = 12;
+
+/// Maximum number of fields expanded for one value
+///
+/// Fields referencing the same field several times would otherwise grow
+/// exponentially with the depth.
+pub const MAX_EXPANSIONS: usize = 1000;
+
+/// Entry field that can be referenced
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    Title,
+    UserName,
+    Password,
+    Url,
+    Notes,
+    Custom(String),
+}
+
+/// Fields being expanded and the number of expansions so far
+#[derive(Default)]
+struct State {
+    stack: Vec<(String, Field)>,
+    expansions: usize,
+}
+
+/// Resolves placeholders against the entries of a vault
+pub struct Resolver<'a> {
+    entries: &'a [Entry],
+    now: DateTime<Utc>,
+}
+
+impl<'a> Resolver<'a> {
+    /// Create a resolver looking up references in `entries`
+    pub fn new(entries: &'a [Entry]) -> Self {
+        Self {
+            entries,
+            now: Utc::now(),
+        }
+    }
+
+    /// Expand placeholders in `text` in the context of `entry`
+    pub fn resolve(&self, entry: &Entry, text: &str) -> String {
+        self.expand(entry, text, &mut State::default())
+    }
+
+    /// Copy of `entry` with placeholders in all fields expanded
+    pub fn resolve_entry(&self, entry: &Entry) -> Entry {
+        let mut resolved = entry.clone();
+        resolved.title = self.field(entry, &Field::Title, &mut State::default());
+        resolved.username = self.field(entry, &Field::UserName, &mut State::default());
+        resolved.password = self.field(entry, &Field::Password, &mut State::default());
+        resolved.url = self.field(entry, &Field::Url, &mut State::default());
+        resolved.notes = self.field(entry, &Field::Notes, &mut State::default());
+        for field in &mut resolved.custom_fields {
+            field.value = self.field(
+                entry,
+                &Field::Custom(field.key.clone()),
+                &mut State::default(),
+            );
+        }
+        resolved
+    }
+
+    /// Resolved value of a field, or its raw value on a cycle or at a limit
+    fn field(&self, entry: &Entry, field: &Field, state: &mut State) -> String {
+        let raw = raw_value(entry, field).unwrap_or_default();
+        let key = (entry.id.clone(), field.clone());
+        if state.stack.len() >= MAX_DEPTH
+            || state.expansions >= MAX_EXPANSIONS
+            || state.stack.contains(&key)
+        {
+            return raw.to_string();
+        }
+
+        state.expansions += 1;
+        state.stack.push(key);
+        let value = self.expand(entry, raw, state);
+        state.stack.pop();
+        value
+    }
+
+    fn expand(&self, entry: &Entry, text: &str, state: &mut State) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                out.push_str(&rest[start..]);
+                return out;
+            };
+
+            // A stray brace before a placeholder is kept as text
+            let token = &rest[start + 1..start + len];
+            if let Some(nested) = token.rfind('{') {
+                out.push_str(&rest[start..=start + nested]);
+                rest = &rest[start + nested + 1..];
+                continue;
+            }
+
+            match self.placeholder(entry, token, state) {
+                Some(value) => out.push_str(&value),
+                None => out.push_str(&rest[start..=start + len]),
+            }
+            rest = &rest[start + len + 1..];
+        }
+
+        out.push_str(rest);
+        out
+    }
+
+    /// Value of the placeholder `{token}`, or `None` if it is unknown
+    fn placeholder(&self, entry: &Entry, token: &str, state: &mut State) -> Option<String> {
+        let upper = token.to_uppercase();
+
+        if let Some(reference) = upper.strip_prefix("REF:") {
+            return self.reference(reference, token.get(4..)?, state);
+        }
+        if token.len() > 2 && upper.starts_with("S:") {
+            let field = Field::Custom(custom_key(entry, token.get(2..)?)?.to_string());
+            return Some(self.field(entry, &field, state));
+        }
+        if let Some(part) = upper.strip_prefix("URL:") {
+            let url = self.field(entry, &Field::Url, state);
+            return url_part(&url, part);
+        }
+        if let Some(format) = upper.strip_prefix("DT_UTC_") {
+            return date_time(self.now, format);
+        }
+        if let Some(format) = upper.strip_prefix("DT_") {
+            return date_time(self.now.with_timezone(&Local), format);
+        }
+
+        let field = match upper.as_str() {
+            "TITLE" => Field::Title,
+            "USERNAME" => Field::UserName,
+            "PASSWORD" => Field::Password,
+            "URL" => Field::Url,
+            "NOTES" => Field::Notes,
+            "UUID" => return Some(kdbx_uuid(&entry.id)),
+            _ => return None,
+        };
+        Some(self.field(entry, &field, state))
+    }
+
+    /// Resolve `<wanted>@<search in>:<text>`, e.g. `P@I:<uuid>`
+    fn reference(&self, upper: &str, original: &str, state: &mut State) -> Option<String> {
+        let mut chars = upper.chars();
+        let (wanted, at, search_in, colon) =
+            (chars.next()?, chars.next()?, chars.next()?, chars.next()?);
+        if !wanted.is_ascii() || at != '@' || !search_in.is_ascii() || colon != ':' {
+            return None;
+        }
+        let text = original.get(4..)?;
+
+        let target = self.entries.iter().find(|e| match search_in {
+            'I' => kdbx_uuid(&e.id) == kdbx_uuid(text),
+            'O' => e.custom_fields.iter().any(|f| contains(&f.value, text)),
+            _ => field_for(search_in)
+                .and_then(|field| raw_value(e, &field))
+                .is_some_and(|value| contains(value, text)),
+        })?;
+
+        if wanted == 'I' {
+            return Some(kdbx_uuid(&target.id));
+        }
+        Some(self.field(target, &field_for(wanted)?, state))
+    }
+}
+
+fn field_for(code: char) -> Option<Field> {
+    match code {
+        'T' => Some(Field::Title),
+        'U' => Some(Field::UserName),
+        'P' => Some(Field::Password),
+        'A' => Some(Field::Url),
+        'N' => Some(Field::Notes),
+        _ => None,
+    }
+}
+
+fn raw_value<'e>(entry: &'e Entry, field: &Field) -> Option<&'e str> {
+    match field {
+        Field::Title => Some(&entry.title),
+        Field::UserName => Some(&entry.username),
+        Field::Password => Some(&entry.password),
+        Field::Url => Some(&entry.url),
+        Field::Notes => Some(&entry.notes),
+        Field::Custom(key) => entry
+            .custom_fields
+            .iter()
+            .find(|f| &f.key == key)
+            .map(|f| f.value.as_str()),
+    }
+}
+
+/// Custom field name matching `name`, preferring an exact match
+fn custom_key<'e>(entry: &'e Entry, name: &str) -> Option<&'e str> {
+    let fields = &entry.custom_fields;
+    fields
+        .iter()
+        .find(|f| f.key == name)
+        .or_else(|| fields.iter().find(|f| f.key.eq_ignore_ascii_case(name)))
+        .map(|f| f.key.as_str())
+}
+
+fn contains(value: &str, text: &str) -> bool {
+    value.to_lowercase().contains(&text.to_lowercase())
+}
+
+/// UUID in the KeePass reference format: 32 uppercase hex digits
+fn kdbx_uuid(id: &str) -> String {
+    id.replace('-', "").to_uppercase()
+}
+
+fn date_time<Tz: chrono::TimeZone>(now: DateTime<Tz>, format: &str) -> Option<String>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let pattern = match format {
+        "SIMPLE" => "%Y%m%d%H%M%S",
+        "YEAR" => "%Y",
+        "MONTH" => "%m",
+        "DAY" => "%d",
+        "HOUR" => "%H",
+        "MINUTE" => "%M",
+        "SECOND" => "%S",
+        _ => return None,
+    };
+    Some(now.format(pattern).to_string())
+}
+
+/// Component of a URL for `{URL:<part>}`
+fn url_part(url: &str, part: &str) -> Option<String> {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None => ("", url),
+    };
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, path_and_more) = rest.split_at(authority_end);
+    let (user_info, host_port) = match authority.rsplit_once('@') {
+        Some((user_info, host_port)) => (user_info, host_port),
+        None => ("", authority),
+    };
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (host_port, None),
+    };
+    let path_end = path_and_more
+        .find(['?', '#'])
+        .unwrap_or(path_and_more.len());
+    let path = &path_and_more[..path_end];
+    let query = path_and_more[path_end..]
+        .split('#')
+        .next()
+        .unwrap_or_default();
+
+    let value = match part {
+        "RMVSCM" => rest.to_string(),
+        "SCM" => scheme.to_string(),
+        "HOST" => host.to_string(),
+        "PORT" => port
+            .map(str::to_string)
+            .or_else(|| default_port(scheme).map(|p| p.to_string()))
+            .unwrap_or_default(),
+        "PATH" => path.to_string(),
+        "QUERY" => query.to_string(),
+        "USERINFO" => user_info.to_string(),
+        "USERNAME" => user_info.split(':').next().unwrap_or_default().to_string(),
+        "PASSWORD" => user_info
+            .split_once(':')
+            .map(|(_, p)| p.to_string())
+            .unwrap_or_default(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme.to_lowercase().as_str() {
+        "http" => Some(80),
+        "https" => Some(443),
+        "ftp" => Some(21),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::CustomField;
+
+    fn entry(title: &str, username: &str, password: &str) -> Entry {
+        let mut entry = Entry::new(title.to_string(), "group".to_string());
+        entry.username = username.to_string();
+        entry.password = password.to_string();
+        entry
+    }
+
+    #[test]
+    fn test_standard_placeholders() {
+        let mut e = entry("Mail", "alice", "secret");
+        e.url = "https://bob:pw@mail.example.com:8443/inbox?folder=1#top".to_string();
+        e.custom_fields.push(CustomField {
+            key: "PIN".to_string(),
+            value: "1234".to_string(),
+            protected: true,
+        });
+        let entries = vec![e.clone()];
+        let resolver = Resolver::new(&entries);
+
+        assert_eq!(
+            resolver.resolve(&e, "{username}/{PASSWORD}/{S:pin}/{UNKNOWN}"),
+            "alice/secret/1234/{UNKNOWN}"
+        );
+        assert_eq!(
+            resolver.resolve(&e, "{URL:SCM} {URL:HOST} {URL:PORT} {URL:PATH} {URL:QUERY}"),
+            "https mail.example.com 8443 /inbox ?folder=1"
+        );
+        assert_eq!(resolver.resolve(&e, "{URL:USERINFO}"), "bob:pw");
+        assert_eq!(resolver.resolve(&e, "{DT_UTC_SIMPLE}").len(), 14);
+        assert_eq!(resolver.resolve(&e, "open {brace"), "open {brace");
+        assert_eq!(resolver.resolve(&e, "{ {TITLE}"), "{ Mail");
+    }
+
+    #[test]
+    fn test_references() {
+        let target = entry("Server", "root", "hunter2");
+        let uuid = kdbx_uuid(&target.id);
+        let mut alias = entry("Alias", "", "");
+        alias.username = format!("{{REF:U@I:{}}}", uuid);
+        alias.password = format!("{{REF:P@I:{}}}", uuid);
+        alias.notes = "Password of {REF:T@U:root}: {REF:P@T:server}".to_string();
+        let entries = vec![target, alias.clone()];
+        let resolver = Resolver::new(&entries);
+
+        let resolved = resolver.resolve_entry(&alias);
+        assert_eq!(resolved.username, "root");
+        assert_eq!(resolved.password, "hunter2");
+        assert_eq!(resolved.notes, "Password of Server: hunter2");
+    }
+
+    #[test]
+    fn test_cycles_are_left_unresolved() {
+        let mut a = entry("A", "", "");
+        let mut b = entry("B", "", "");
+        a.password = format!("{{REF:P@I:{}}}", kdbx_uuid(&b.id));
+        b.password = format!("{{REF:P@I:{}}}", kdbx_uuid(&a.id));
+        a.username = "{USERNAME}".to_string();
+        let entries = vec![a.clone(), b];
+        let resolver = Resolver::new(&entries);
+
+        let resolved = resolver.resolve_entry(&a);
+        assert_eq!(resolved.username, "{USERNAME}");
+        assert_eq!(resolved.password, a.password);
+    }
+
+    #[test]
+    fn test_repeated_references_are_bounded() {
+        // Each entry references the next one's password four times
+        let mut entries: Vec<Entry> = (0..MAX_DEPTH + 2)
+            .map(|i| entry(&i.to_string(), "", "x"))
+            .collect();
+        for i in 0..entries.len() - 1 {
+            let reference = format!("{{REF:P@I:{}}}", kdbx_uuid(&entries[i + 1].id));
+            entries[i].password = reference.repeat(4);
+        }
+        let resolver = Resolver::new(&entries);
+
+        let resolved = resolver.resolve(&entries[0], "{PASSWORD}");
+        // Unbounded, this would be 4^(MAX_DEPTH + 1) copies of "x". Now each
+        // expanded field adds at most its four references left as they are.
+        let raw = entries[0].password.len();
+        assert!(resolved.len() <= 4 * MAX_EXPANSIONS * raw);
+    }
+}
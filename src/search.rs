@@ -1,6 +1,14 @@
 // Search and filter module
 
 use crate::entry::Entry;
+use std::collections::HashMap;
+
+/// Tag with the number of entries using it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagCount {
+    pub name: String,
+    pub count: usize,
+}
 
 /// Search entries by query
 pub fn search_entries(entries: &[Entry], query: &str) -> Vec<Entry> {
@@ -27,6 +35,30 @@ pub fn filter_by_tag(entries: &[Entry], tag: &str) -> Vec<Entry> {
         .collect()
 }
 
+/// List the tags used by entries with their counts, sorted by name
+pub fn list_tags(entries: &[Entry]) -> Vec<TagCount> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for tag in entries.iter().flat_map(|entry| &entry.tags) {
+        *counts.entry(tag.as_str()).or_default() += 1;
+    }
+
+    let mut tags: Vec<TagCount> = counts
+        .into_iter()
+        .map(|(name, count)| TagCount {
+            name: name.to_string(),
+            count,
+        })
+        .collect();
+    tags.sort_by(|a, b| {
+        a.name
+            .to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    tags
+}
+
 /// Get favorite entries
 pub fn get_favorites(entries: &[Entry]) -> Vec<Entry> {
     entries
@@ -51,4 +83,16 @@ mod tests {
         let results = search_entries(&entries, "nonexistent");
         assert_eq!(results.len(), 0);
     }
+
+    #[test]
+    fn test_list_tags() {
+        let mut work = Entry::new("Work".to_string(), "group-1".to_string());
+        work.tags = vec!["work".to_string(), "email".to_string()];
+        let mut home = Entry::new("Home".to_string(), "group-1".to_string());
+        home.tags = vec!["Email".to_string(), "email".to_string()];
+
+        let tags = list_tags(&[work, home]);
+        let names: Vec<(&str, usize)> = tags.iter().map(|t| (t.name.as_str(), t.count)).collect();
+        assert_eq!(names, vec![("Email", 1), ("email", 2), ("work", 1)]);
+    }
 }
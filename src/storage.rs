@@ -0,0 +1,408 @@
+// Storage module
+//
+// Pluggable persistence for encrypted vault data. Local files are written
+// crash-safe: writes go to a temporary file in the target directory which is
+// synced and atomically renamed over the database.
+
+use crate::crypto::CryptoUtils;
+use crate::error::{Result, VaultError};
+use chrono::Utc;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Rotating backup settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupPolicy {
+    /// Number of previous versions to keep
+    pub max_backups: usize,
+
+    /// Directory for backups (defaults to the database directory)
+    pub directory: Option<PathBuf>,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            max_backups: 5,
+            directory: None,
+        }
+    }
+}
+
+/// Opaque revision of stored vault data (content hash, ETag, ...)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Revision(String);
+
+impl Revision {
+    /// Wrap a backend-specific revision string
+    pub fn new<S: Into<String>>(revision: S) -> Self {
+        Self(revision.into())
+    }
+
+    /// Get the revision string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Backend that stores the encrypted vault
+///
+/// Implementations must replace the stored data atomically, so readers see
+/// either the old or the new database, and refuse to overwrite data that
+/// changed since the revision the caller last saw.
+pub trait VaultStorage: Send {
+    /// Human-readable location (file path or URL)
+    fn location(&self) -> String;
+
+    /// Local file path, if the backend stores a file on this machine
+    ///
+    /// Enables rotating backups and advisory lock files.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Load the encrypted vault and its revision
+    fn load(&mut self) -> Result<(Vec<u8>, Revision)>;
+
+    /// Get the current revision (None if nothing is stored)
+    fn revision(&self) -> Result<Option<Revision>>;
+
+    /// Atomically replace the stored vault and return the new revision
+    ///
+    /// With `expected` set, fails with `VaultError::ExternalModification` if
+    /// the stored revision differs. Without it, overwrites unconditionally.
+    fn save(&mut self, data: &[u8], expected: Option<&Revision>) -> Result<Revision>;
+
+    /// Check if the stored vault changed since `since`
+    fn has_changed(&self, since: &Revision) -> Result<bool> {
+        Ok(self.revision()?.is_some_and(|current| current != *since))
+    }
+}
+
+/// Vault stored in a local file
+#[derive(Debug)]
+pub struct LocalFileStorage {
+    path: PathBuf,
+    fingerprint: Option<FileFingerprint>,
+}
+
+impl LocalFileStorage {
+    /// Store the vault at `path`
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            fingerprint: None,
+        }
+    }
+}
+
+impl VaultStorage for LocalFileStorage {
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    fn load(&mut self) -> Result<(Vec<u8>, Revision)> {
+        let (data, fingerprint) = read_file(&self.path)?;
+        let revision = fingerprint.revision();
+        self.fingerprint = Some(fingerprint);
+
+        Ok((data, revision))
+    }
+
+    fn revision(&self) -> Result<Option<Revision>> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(Revision(to_hex(&CryptoUtils::sha256(&data))))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&mut self, data: &[u8], expected: Option<&Revision>) -> Result<Revision> {
+        if let Some(expected) = expected {
+            if self.has_changed(expected)? {
+                return Err(VaultError::ExternalModification(self.location()));
+            }
+        }
+
+        let fingerprint = write_atomic(&self.path, data)?;
+        let revision = fingerprint.revision();
+        self.fingerprint = Some(fingerprint);
+
+        Ok(revision)
+    }
+
+    fn has_changed(&self, since: &Revision) -> Result<bool> {
+        // Skip hashing the file while its size and modification time match
+        match &self.fingerprint {
+            Some(fingerprint) if fingerprint.revision() == *since => {
+                fingerprint.has_changed(&self.path)
+            }
+            _ => Ok(self.revision()?.is_some_and(|current| current != *since)),
+        }
+    }
+}
+
+/// Snapshot of a database file used to detect changes made by other programs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileFingerprint {
+    modified: Option<SystemTime>,
+    size: u64,
+    hash: [u8; 32],
+}
+
+impl FileFingerprint {
+    /// Fingerprint file contents together with their metadata
+    pub(crate) fn new(data: &[u8], metadata: &fs::Metadata) -> Self {
+        Self {
+            modified: metadata.modified().ok(),
+            size: data.len() as u64,
+            hash: CryptoUtils::sha256(data),
+        }
+    }
+
+    /// Check if the file at `path` differs from this fingerprint
+    ///
+    /// The contents are only hashed when the size or modification time
+    /// changed, so touching a file without modifying it is not a change. A
+    /// missing file is not reported as changed.
+    pub(crate) fn has_changed(&self, path: &Path) -> Result<bool> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+
+        if metadata.len() == self.size && metadata.modified().ok() == self.modified {
+            return Ok(false);
+        }
+
+        let data = fs::read(path)?;
+        Ok(CryptoUtils::sha256(&data) != self.hash)
+    }
+
+    fn revision(&self) -> Revision {
+        Revision(to_hex(&self.hash))
+    }
+}
+
+/// Read a database file and fingerprint it
+pub(crate) fn read_file(path: &Path) -> Result<(Vec<u8>, FileFingerprint)> {
+    let mut file = File::open(path)
+        .map_err(|e| VaultError::OpenError(format!("Failed to open file: {}", e)))?;
+
+    let mut data = Vec::new();
+    std::io::Read::read_to_end(&mut file, &mut data)
+        .map_err(|e| VaultError::OpenError(format!("Failed to read file: {}", e)))?;
+    let fingerprint = FileFingerprint::new(&data, &file.metadata()?);
+
+    Ok((data, fingerprint))
+}
+
+/// Atomically replace the file at `path` with `data`
+///
+/// The data is written and synced to a temporary file in the same directory,
+/// which is then renamed over `path`. A crash leaves either the old or the new
+/// file in place, never a truncated one.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<FileFingerprint> {
+    let dir = parent_dir(path);
+    let file_name = file_name(path)?;
+    let tmp_path = dir.join(format!(".{}.tmp-{}", file_name, uuid::Uuid::new_v4()));
+
+    let result = (|| -> std::io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        drop(file);
+
+        // Keep the permissions of the file we replace
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&tmp_path, metadata.permissions())?;
+        }
+
+        fs::rename(&tmp_path, path)?;
+        sync_dir(&dir)
+    })();
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(VaultError::SaveError(format!(
+            "Failed to write {}: {}",
+            path.display(),
+            e
+        )));
+    }
+
+    Ok(FileFingerprint::new(data, &fs::metadata(path)?))
+}
+
+/// Timestamp in backup names, `<file>.<timestamp>.bak`
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+
+/// Copy the current file at `path` into a timestamped backup and prune old ones
+///
+/// Backups are named `<file name>.<timestamp>.bak`. Does nothing if `path`
+/// does not exist yet.
+pub(crate) fn backup_file(path: &Path, policy: &BackupPolicy) -> Result<()> {
+    if policy.max_backups == 0 || !path.exists() {
+        return Ok(());
+    }
+
+    let dir = policy.directory.clone().unwrap_or_else(|| parent_dir(path));
+    let file_name = file_name(path)?;
+
+    fs::create_dir_all(&dir)?;
+    let timestamp = Utc::now().format(BACKUP_TIMESTAMP_FORMAT);
+    let backup_path = dir.join(format!("{}.{}.bak", file_name, timestamp));
+    fs::copy(path, &backup_path)
+        .map_err(|e| VaultError::SaveError(format!("Failed to create backup: {}", e)))?;
+
+    let mut backups = list_backups(path, policy)?;
+    if backups.len() > policy.max_backups {
+        let excess = backups.len() - policy.max_backups;
+        for old in backups.drain(..excess) {
+            fs::remove_file(old)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// List the backups of `path`, oldest first
+pub fn list_backups(path: &Path, policy: &BackupPolicy) -> Result<Vec<PathBuf>> {
+    let dir = policy.directory.clone().unwrap_or_else(|| parent_dir(path));
+    let prefix = format!("{}.", file_name(path)?);
+    let is_backup = |name: &str| {
+        name.strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(".bak"))
+            .is_some_and(is_backup_timestamp)
+    };
+
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(is_backup)
+        })
+        .collect();
+
+    // Timestamps sort lexicographically
+    backups.sort();
+
+    Ok(backups)
+}
+
+/// Check for a timestamp written with `BACKUP_TIMESTAMP_FORMAT`
+fn is_backup_timestamp(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 18
+        && bytes[8] == b'-'
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 8 || b.is_ascii_digit())
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.to_string())
+        .ok_or_else(|| VaultError::SaveError(format!("Invalid vault path: {}", path.display())))
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    // Persist the rename itself
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.kdbx");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_fingerprint_detects_changes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.kdbx");
+
+        let fingerprint = write_atomic(&path, b"original").unwrap();
+        assert!(!fingerprint.has_changed(&path).unwrap());
+
+        fs::write(&path, b"modified elsewhere").unwrap();
+        assert!(fingerprint.has_changed(&path).unwrap());
+
+        // Rewriting identical contents is not a change
+        fs::write(&path, b"original").unwrap();
+        assert!(!fingerprint.has_changed(&path).unwrap());
+    }
+
+    #[test]
+    fn test_backup_rotation() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.kdbx");
+        let policy = BackupPolicy {
+            max_backups: 2,
+            directory: Some(dir.path().join("backups")),
+        };
+
+        // Files that merely look like backups are left alone
+        fs::create_dir(dir.path().join("backups")).unwrap();
+        let manual = dir.path().join("backups").join("vault.kdbx.manual.bak");
+        fs::write(&manual, "manual").unwrap();
+
+        for i in 0..4 {
+            fs::write(&path, format!("version {}", i)).unwrap();
+            backup_file(&path, &policy).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let backups = list_backups(&path, &policy).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), "version 2");
+        assert_eq!(fs::read_to_string(&backups[1]).unwrap(), "version 3");
+        assert!(manual.exists());
+        assert!(is_backup_timestamp("20261017-071911123"));
+        assert!(!is_backup_timestamp("20261017-07191112"));
+    }
+}
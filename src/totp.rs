@@ -131,7 +131,7 @@ pub fn encode_base32(input: &[u8]) -> String {
     }
 
     // Add padding
-    while result.len() % 8 != 0 {
+    while !result.len().is_multiple_of(8) {
         result.push('=');
     }
 
@@ -2,12 +2,14 @@
 //
 // This module provides the FFI layer for iOS/Swift integration using proc-macros
 
+use crate::key::CompositeKey;
 use crate::search;
 use crate::totp;
 use crate::vault::Vault as CoreVault;
 use crate::{VaultConfig as CoreVaultConfig, VaultError as CoreVaultError};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 // ============================================================================
 // Type Definitions for UniFFI
@@ -30,11 +32,13 @@ pub struct Entry {
     pub tags: Vec<String>,
     pub totp_secret: Option<String>,
     pub custom_fields: Vec<CustomField>,
-    pub created_at: i64,         // Unix timestamp
-    pub modified_at: i64,        // Unix timestamp
-    pub accessed_at: i64,        // Unix timestamp
+    pub created_at: i64,  // Unix timestamp
+    pub modified_at: i64, // Unix timestamp
+    pub accessed_at: i64, // Unix timestamp
+    pub usage_count: u64,
     pub expires_at: Option<i64>, // Unix timestamp
     pub is_favorite: bool,
+    pub custom_icon_id: Option<String>,
 }
 
 /// Group data structure for UniFFI
@@ -44,6 +48,7 @@ pub struct Group {
     pub parent_id: Option<String>,
     pub name: String,
     pub icon_id: u32,
+    pub custom_icon_id: Option<String>,
     pub notes: String,
     pub is_recycle_bin: bool,
     pub is_expanded: bool,
@@ -57,12 +62,140 @@ pub struct CustomField {
     pub protected: bool,
 }
 
+/// Key derivation function
+#[derive(uniffi::Enum, Clone, Copy)]
+pub enum KdfAlgorithm {
+    Argon2d,
+    Argon2id,
+    AesKdf,
+}
+
+/// Outer encryption cipher
+#[derive(uniffi::Enum, Clone, Copy)]
+pub enum OuterCipher {
+    Aes256,
+    ChaCha20,
+    Twofish,
+}
+
+/// Inner stream cipher
+#[derive(uniffi::Enum, Clone, Copy)]
+pub enum InnerCipher {
+    ChaCha20,
+    Salsa20,
+    Plain,
+}
+
+/// Payload compression
+#[derive(uniffi::Enum, Clone, Copy)]
+pub enum Compression {
+    None,
+    GZip,
+}
+
 /// Vault configuration
 #[derive(uniffi::Record, Clone)]
 pub struct VaultConfig {
+    pub kdf: KdfAlgorithm,
     pub kdf_iterations: u64,
     pub argon2_memory: u64,
     pub argon2_parallelism: u32,
+    pub cipher: OuterCipher,
+    pub inner_cipher: InnerCipher,
+    pub compression: Compression,
+}
+
+/// Platform events that can lock the vault
+#[derive(uniffi::Enum, Clone, Copy)]
+pub enum LockTrigger {
+    ScreenLocked,
+    AppBackgrounded,
+    SystemSleep,
+    UserSwitched,
+}
+
+/// Auto-lock policy (timeouts in seconds)
+#[derive(uniffi::Record, Clone)]
+pub struct AutoLockPolicy {
+    pub idle_timeout_secs: Option<u64>,
+    pub session_timeout_secs: Option<u64>,
+    pub lock_on: Vec<LockTrigger>,
+}
+
+/// Database name, color and other database-wide settings
+#[derive(uniffi::Record, Clone)]
+pub struct VaultMetadata {
+    pub name: String,
+    pub description: String,
+    pub default_username: String,
+    pub color: Option<String>, // #RRGGBB
+    pub generator: String,
+    pub maintenance_history_days: Option<u32>,
+    pub master_key_changed: Option<i64>, // Unix timestamp, read-only
+    pub master_key_change_rec: Option<u32>,
+    pub master_key_change_force: Option<u32>,
+}
+
+/// Permanently deleted entry or group
+#[derive(uniffi::Record, Clone)]
+pub struct DeletedObject {
+    pub id: String,
+    pub deleted_at: i64, // Unix timestamp
+}
+
+/// Recycle bin retention policy
+#[derive(uniffi::Record, Clone)]
+pub struct RecycleBinPolicy {
+    pub max_age_days: Option<u32>,
+    pub max_items: Option<u64>,
+}
+
+/// File attached to an entry
+#[derive(uniffi::Record, Clone)]
+pub struct Attachment {
+    pub name: String,
+    pub size: u64,
+    pub protected: bool,
+}
+
+/// Entry or group in the recycle bin
+#[derive(uniffi::Record, Clone)]
+pub struct RecycledItem {
+    pub id: String,
+    pub name: String,
+    pub is_group: bool,
+    pub deleted_at: Option<i64>, // Unix timestamp
+    pub previous_parent_id: Option<String>,
+}
+
+/// Custom icon image (usually PNG)
+#[derive(uniffi::Record, Clone)]
+pub struct CustomIcon {
+    pub id: String,
+    pub data: Vec<u8>,
+}
+
+/// Tag with the number of entries using it
+#[derive(uniffi::Record, Clone)]
+pub struct TagCount {
+    pub name: String,
+    pub count: u32,
+}
+
+/// Result of merging another vault (entry and group IDs)
+#[derive(uniffi::Record, Clone)]
+pub struct MergeReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+/// Vault credentials (password and/or key file)
+#[derive(uniffi::Record, Clone)]
+pub struct Credentials {
+    pub password: Option<String>,
+    pub key_file_path: Option<String>,
 }
 
 /// Error type for UniFFI
@@ -70,18 +203,34 @@ pub struct VaultConfig {
 pub enum VaultError {
     #[error("Vault is locked")]
     VaultLocked,
+    #[error("Vault file was modified externally")]
+    ExternalModification,
+    #[error("Vault is in use by another application")]
+    VaultInUse,
+    #[error("Quick unlock unavailable")]
+    QuickUnlockUnavailable,
     #[error("Invalid password")]
     InvalidPassword,
+    #[error("Invalid key file")]
+    InvalidKeyFile,
     #[error("Entry not found")]
     EntryNotFound,
     #[error("Group not found")]
     GroupNotFound,
+    #[error("Custom icon not found")]
+    IconNotFound,
+    #[error("Attachment not found")]
+    AttachmentNotFound,
+    #[error("Invalid move")]
+    InvalidMove,
     #[error("Invalid entry")]
     InvalidEntry,
     #[error("Encryption error")]
     EncryptionError,
     #[error("Decryption error")]
     DecryptionError,
+    #[error("Invalid vault config")]
+    InvalidConfig,
     #[error("IO error")]
     IoError,
     #[error("Serialization error")]
@@ -112,8 +261,10 @@ impl From<crate::Entry> for Entry {
             created_at: e.created_at.timestamp(),
             modified_at: e.modified_at.timestamp(),
             accessed_at: e.accessed_at.timestamp(),
+            usage_count: e.usage_count,
             expires_at: e.expires_at.map(|t| t.timestamp()),
             is_favorite: e.is_favorite,
+            custom_icon_id: e.custom_icon_id.clone(),
         }
     }
 }
@@ -135,8 +286,10 @@ impl From<Entry> for crate::Entry {
             created_at: Utc.timestamp_opt(e.created_at, 0).unwrap(),
             modified_at: Utc.timestamp_opt(e.modified_at, 0).unwrap(),
             accessed_at: Utc.timestamp_opt(e.accessed_at, 0).unwrap(),
+            usage_count: e.usage_count,
             expires_at: e.expires_at.map(|t| Utc.timestamp_opt(t, 0).unwrap()),
             is_favorite: e.is_favorite,
+            custom_icon_id: e.custom_icon_id,
         }
     }
 }
@@ -148,6 +301,7 @@ impl From<crate::Group> for Group {
             parent_id: g.parent_id,
             name: g.name,
             icon_id: g.icon_id,
+            custom_icon_id: g.custom_icon_id,
             notes: g.notes,
             is_recycle_bin: g.is_recycle_bin,
             is_expanded: g.is_expanded,
@@ -162,6 +316,7 @@ impl From<Group> for crate::Group {
             parent_id: g.parent_id,
             name: g.name,
             icon_id: g.icon_id,
+            custom_icon_id: g.custom_icon_id,
             notes: g.notes,
             is_recycle_bin: g.is_recycle_bin,
             is_expanded: g.is_expanded,
@@ -189,13 +344,280 @@ impl From<CustomField> for crate::CustomField {
     }
 }
 
+impl From<KdfAlgorithm> for crate::KdfAlgorithm {
+    fn from(k: KdfAlgorithm) -> Self {
+        match k {
+            KdfAlgorithm::Argon2d => crate::KdfAlgorithm::Argon2d,
+            KdfAlgorithm::Argon2id => crate::KdfAlgorithm::Argon2id,
+            KdfAlgorithm::AesKdf => crate::KdfAlgorithm::AesKdf,
+        }
+    }
+}
+
+impl From<crate::KdfAlgorithm> for KdfAlgorithm {
+    fn from(k: crate::KdfAlgorithm) -> Self {
+        match k {
+            crate::KdfAlgorithm::Argon2d => KdfAlgorithm::Argon2d,
+            crate::KdfAlgorithm::Argon2id => KdfAlgorithm::Argon2id,
+            crate::KdfAlgorithm::AesKdf => KdfAlgorithm::AesKdf,
+        }
+    }
+}
+
+impl From<OuterCipher> for crate::OuterCipher {
+    fn from(c: OuterCipher) -> Self {
+        match c {
+            OuterCipher::Aes256 => crate::OuterCipher::Aes256,
+            OuterCipher::ChaCha20 => crate::OuterCipher::ChaCha20,
+            OuterCipher::Twofish => crate::OuterCipher::Twofish,
+        }
+    }
+}
+
+impl From<crate::OuterCipher> for OuterCipher {
+    fn from(c: crate::OuterCipher) -> Self {
+        match c {
+            crate::OuterCipher::Aes256 => OuterCipher::Aes256,
+            crate::OuterCipher::ChaCha20 => OuterCipher::ChaCha20,
+            crate::OuterCipher::Twofish => OuterCipher::Twofish,
+        }
+    }
+}
+
+impl From<InnerCipher> for crate::InnerCipher {
+    fn from(c: InnerCipher) -> Self {
+        match c {
+            InnerCipher::ChaCha20 => crate::InnerCipher::ChaCha20,
+            InnerCipher::Salsa20 => crate::InnerCipher::Salsa20,
+            InnerCipher::Plain => crate::InnerCipher::Plain,
+        }
+    }
+}
+
+impl From<crate::InnerCipher> for InnerCipher {
+    fn from(c: crate::InnerCipher) -> Self {
+        match c {
+            crate::InnerCipher::ChaCha20 => InnerCipher::ChaCha20,
+            crate::InnerCipher::Salsa20 => InnerCipher::Salsa20,
+            crate::InnerCipher::Plain => InnerCipher::Plain,
+        }
+    }
+}
+
+impl From<Compression> for crate::Compression {
+    fn from(c: Compression) -> Self {
+        match c {
+            Compression::None => crate::Compression::None,
+            Compression::GZip => crate::Compression::GZip,
+        }
+    }
+}
+
+impl From<crate::Compression> for Compression {
+    fn from(c: crate::Compression) -> Self {
+        match c {
+            crate::Compression::None => Compression::None,
+            crate::Compression::GZip => Compression::GZip,
+        }
+    }
+}
+
 impl From<VaultConfig> for CoreVaultConfig {
     fn from(c: VaultConfig) -> Self {
         CoreVaultConfig {
+            kdf: c.kdf.into(),
             kdf_iterations: c.kdf_iterations,
             argon2_memory: c.argon2_memory,
             argon2_parallelism: c.argon2_parallelism,
+            cipher: c.cipher.into(),
+            inner_cipher: c.inner_cipher.into(),
+            compression: c.compression.into(),
+        }
+    }
+}
+
+impl From<CoreVaultConfig> for VaultConfig {
+    fn from(c: CoreVaultConfig) -> Self {
+        VaultConfig {
+            kdf: c.kdf.into(),
+            kdf_iterations: c.kdf_iterations,
+            argon2_memory: c.argon2_memory,
+            argon2_parallelism: c.argon2_parallelism,
+            cipher: c.cipher.into(),
+            inner_cipher: c.inner_cipher.into(),
+            compression: c.compression.into(),
+        }
+    }
+}
+
+impl From<LockTrigger> for crate::LockTrigger {
+    fn from(t: LockTrigger) -> Self {
+        match t {
+            LockTrigger::ScreenLocked => crate::LockTrigger::ScreenLocked,
+            LockTrigger::AppBackgrounded => crate::LockTrigger::AppBackgrounded,
+            LockTrigger::SystemSleep => crate::LockTrigger::SystemSleep,
+            LockTrigger::UserSwitched => crate::LockTrigger::UserSwitched,
+        }
+    }
+}
+
+impl From<crate::LockTrigger> for LockTrigger {
+    fn from(t: crate::LockTrigger) -> Self {
+        match t {
+            crate::LockTrigger::ScreenLocked => LockTrigger::ScreenLocked,
+            crate::LockTrigger::AppBackgrounded => LockTrigger::AppBackgrounded,
+            crate::LockTrigger::SystemSleep => LockTrigger::SystemSleep,
+            crate::LockTrigger::UserSwitched => LockTrigger::UserSwitched,
+        }
+    }
+}
+
+impl From<AutoLockPolicy> for crate::AutoLockPolicy {
+    fn from(p: AutoLockPolicy) -> Self {
+        crate::AutoLockPolicy {
+            idle_timeout: p.idle_timeout_secs.map(Duration::from_secs),
+            session_timeout: p.session_timeout_secs.map(Duration::from_secs),
+            lock_on: p.lock_on.into_iter().map(|t| t.into()).collect(),
+        }
+    }
+}
+
+impl From<crate::AutoLockPolicy> for AutoLockPolicy {
+    fn from(p: crate::AutoLockPolicy) -> Self {
+        AutoLockPolicy {
+            idle_timeout_secs: p.idle_timeout.map(|d| d.as_secs()),
+            session_timeout_secs: p.session_timeout.map(|d| d.as_secs()),
+            lock_on: p.lock_on.into_iter().map(|t| t.into()).collect(),
+        }
+    }
+}
+
+impl From<crate::VaultMetadata> for VaultMetadata {
+    fn from(m: crate::VaultMetadata) -> Self {
+        VaultMetadata {
+            name: m.name,
+            description: m.description,
+            default_username: m.default_username,
+            color: m.color,
+            generator: m.generator,
+            maintenance_history_days: m.maintenance_history_days,
+            master_key_changed: m.master_key_changed.map(|t| t.timestamp()),
+            master_key_change_rec: m.master_key_change_rec,
+            master_key_change_force: m.master_key_change_force,
+        }
+    }
+}
+
+impl From<VaultMetadata> for crate::VaultMetadata {
+    fn from(m: VaultMetadata) -> Self {
+        use chrono::{TimeZone, Utc};
+
+        crate::VaultMetadata {
+            name: m.name,
+            description: m.description,
+            default_username: m.default_username,
+            color: m.color,
+            generator: m.generator,
+            maintenance_history_days: m.maintenance_history_days,
+            master_key_changed: m
+                .master_key_changed
+                .and_then(|t| Utc.timestamp_opt(t, 0).single()),
+            master_key_change_rec: m.master_key_change_rec,
+            master_key_change_force: m.master_key_change_force,
+        }
+    }
+}
+
+impl From<crate::DeletedObject> for DeletedObject {
+    fn from(object: crate::DeletedObject) -> Self {
+        DeletedObject {
+            id: object.id,
+            deleted_at: object.deleted_at.timestamp(),
+        }
+    }
+}
+
+impl From<RecycleBinPolicy> for crate::RecycleBinPolicy {
+    fn from(p: RecycleBinPolicy) -> Self {
+        crate::RecycleBinPolicy {
+            max_age_days: p.max_age_days,
+            max_items: p.max_items.map(|n| n as usize),
+        }
+    }
+}
+
+impl From<crate::RecycleBinPolicy> for RecycleBinPolicy {
+    fn from(p: crate::RecycleBinPolicy) -> Self {
+        RecycleBinPolicy {
+            max_age_days: p.max_age_days,
+            max_items: p.max_items.map(|n| n as u64),
+        }
+    }
+}
+
+impl From<crate::Attachment> for Attachment {
+    fn from(a: crate::Attachment) -> Self {
+        Attachment {
+            name: a.name,
+            size: a.size as u64,
+            protected: a.protected,
+        }
+    }
+}
+
+impl From<crate::RecycledItem> for RecycledItem {
+    fn from(item: crate::RecycledItem) -> Self {
+        RecycledItem {
+            id: item.id,
+            name: item.name,
+            is_group: item.is_group,
+            deleted_at: item.deleted_at.map(|t| t.timestamp()),
+            previous_parent_id: item.previous_parent_id,
+        }
+    }
+}
+
+impl From<crate::CustomIcon> for CustomIcon {
+    fn from(i: crate::CustomIcon) -> Self {
+        CustomIcon {
+            id: i.id,
+            data: i.data,
+        }
+    }
+}
+
+impl From<crate::TagCount> for TagCount {
+    fn from(t: crate::TagCount) -> Self {
+        TagCount {
+            name: t.name,
+            count: t.count as u32,
+        }
+    }
+}
+
+impl From<crate::MergeReport> for MergeReport {
+    fn from(r: crate::MergeReport) -> Self {
+        MergeReport {
+            added: r.added,
+            updated: r.updated,
+            deleted: r.deleted,
+            conflicts: r.conflicts,
+        }
+    }
+}
+
+impl TryFrom<Credentials> for CompositeKey {
+    type Error = CoreVaultError;
+
+    fn try_from(c: Credentials) -> Result<Self, Self::Error> {
+        let mut key = CompositeKey::new();
+        if let Some(password) = &c.password {
+            key = key.with_password(password);
         }
+        if let Some(path) = &c.key_file_path {
+            key = key.with_key_file(path)?;
+        }
+        Ok(key)
     }
 }
 
@@ -204,13 +626,21 @@ impl From<CoreVaultError> for VaultError {
         match e {
             CoreVaultError::OpenError(_) => VaultError::IoError,
             CoreVaultError::SaveError(_) => VaultError::IoError,
+            CoreVaultError::ExternalModification(_) => VaultError::ExternalModification,
+            CoreVaultError::VaultInUse(_) => VaultError::VaultInUse,
             CoreVaultError::VaultLocked => VaultError::VaultLocked,
+            CoreVaultError::QuickUnlockUnavailable => VaultError::QuickUnlockUnavailable,
             CoreVaultError::InvalidPassword => VaultError::InvalidPassword,
+            CoreVaultError::InvalidKeyFile(_) => VaultError::InvalidKeyFile,
             CoreVaultError::EntryNotFound(_) => VaultError::EntryNotFound,
             CoreVaultError::GroupNotFound(_) => VaultError::GroupNotFound,
+            CoreVaultError::IconNotFound(_) => VaultError::IconNotFound,
+            CoreVaultError::AttachmentNotFound(_) => VaultError::AttachmentNotFound,
+            CoreVaultError::InvalidMove(_) => VaultError::InvalidMove,
             CoreVaultError::InvalidEntry(_) => VaultError::InvalidEntry,
             CoreVaultError::EncryptionError(_) => VaultError::EncryptionError,
             CoreVaultError::DecryptionError(_) => VaultError::DecryptionError,
+            CoreVaultError::InvalidConfig(_) => VaultError::InvalidConfig,
             CoreVaultError::IoError(_) => VaultError::IoError,
             CoreVaultError::SerializationError(_) => VaultError::SerializationError,
             CoreVaultError::KeePassError(_) => VaultError::KeePassError,
@@ -230,22 +660,271 @@ pub struct Vault {
 
 #[uniffi::export]
 impl Vault {
+    /// Change the master password (applied on next save)
+    pub fn change_master_password(
+        &self,
+        current_password: String,
+        new_password: String,
+    ) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .change_credentials(
+                &CompositeKey::from_password(&current_password),
+                CompositeKey::from_password(&new_password),
+            )
+            .map_err(|e| e.into())
+    }
+
+    /// Change the master credentials (applied on next save)
+    pub fn change_credentials(
+        &self,
+        current: Credentials,
+        new_credentials: Credentials,
+    ) -> Result<(), VaultError> {
+        let current: CompositeKey = current.try_into()?;
+        let new_key: CompositeKey = new_credentials.try_into()?;
+        self.inner
+            .lock()
+            .unwrap()
+            .change_credentials(&current, new_key)
+            .map_err(|e| e.into())
+    }
+
+    /// Get the database name, color and other database-wide settings
+    pub fn get_metadata(&self) -> Result<VaultMetadata, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .get_metadata()
+            .map(|m| m.into())
+            .map_err(|e| e.into())
+    }
+
+    /// Change the database-wide settings (applied on next save)
+    pub fn set_metadata(&self, metadata: VaultMetadata) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .set_metadata(&metadata.into())
+            .map_err(|e| e.into())
+    }
+
     /// Lock the vault
     pub fn lock(&self) -> Result<(), VaultError> {
         self.inner.lock().unwrap().lock();
         Ok(())
     }
 
+    /// Unlock a locked vault with its master password
+    pub fn unlock(&self, password: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .unlock(CompositeKey::from_password(&password))
+            .map_err(|e| e.into())
+    }
+
+    /// Unlock a locked vault with a password and/or key file
+    pub fn unlock_with_credentials(&self, credentials: Credentials) -> Result<(), VaultError> {
+        let key: CompositeKey = credentials.try_into()?;
+        self.inner.lock().unwrap().unlock(key).map_err(|e| e.into())
+    }
+
+    /// Enable quick unlock with a short PIN (kept in memory only)
+    pub fn enable_quick_unlock(&self, pin: String, max_attempts: u32) -> Result<(), VaultError> {
+        let config = crate::QuickUnlockConfig {
+            max_attempts,
+            ..Default::default()
+        };
+        self.inner
+            .lock()
+            .unwrap()
+            .enable_quick_unlock(&pin, config)
+            .map_err(|e| e.into())
+    }
+
+    /// Disable quick unlock
+    pub fn disable_quick_unlock(&self) {
+        self.inner.lock().unwrap().disable_quick_unlock();
+    }
+
+    /// Check if quick unlock is available
+    pub fn is_quick_unlock_enabled(&self) -> bool {
+        self.inner.lock().unwrap().is_quick_unlock_enabled()
+    }
+
+    /// Remaining wrong PIN attempts, if quick unlock is enabled
+    pub fn quick_unlock_attempts_remaining(&self) -> Option<u32> {
+        self.inner.lock().unwrap().quick_unlock_attempts_remaining()
+    }
+
+    /// Unlock a locked vault with the quick unlock PIN
+    pub fn quick_unlock(&self, pin: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .quick_unlock(&pin)
+            .map_err(|e| e.into())
+    }
+
+    /// Keep up to `max_backups` previous versions on save (0 disables backups)
+    pub fn set_backup_policy(&self, max_backups: u32, directory: Option<String>) {
+        let policy = (max_backups > 0).then(|| crate::BackupPolicy {
+            max_backups: max_backups as usize,
+            directory: directory.map(PathBuf::from),
+        });
+        self.inner.lock().unwrap().set_backup_policy(policy);
+    }
+
     /// Save the vault
     pub fn save(&self) -> Result<(), VaultError> {
         self.inner.lock().unwrap().save().map_err(|e| e.into())
     }
 
+    /// Save the vault to a new file, which becomes the vault's file
+    pub fn save_as(&self, path: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .save_as(&path)
+            .map_err(|e| e.into())
+    }
+
+    /// Encrypt the vault into bytes (e.g. for platform storage or a sync API)
+    pub fn save_to_bytes(&self) -> Result<Vec<u8>, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .save_to_bytes()
+            .map_err(|e| e.into())
+    }
+
+    /// Get the vault file path (None for in-memory vaults)
+    pub fn get_path(&self) -> Option<String> {
+        self.inner
+            .lock()
+            .unwrap()
+            .path()
+            .map(|p| p.to_string_lossy().into_owned())
+    }
+
+    /// Save the vault, overwriting changes made by other programs
+    pub fn force_save(&self) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .force_save()
+            .map_err(|e| e.into())
+    }
+
+    /// Merge another vault file (e.g. a sync conflict copy) into this vault
+    pub fn merge_from_file(
+        &self,
+        path: String,
+        credentials: Credentials,
+    ) -> Result<MergeReport, VaultError> {
+        let key: CompositeKey = credentials.try_into()?;
+        self.inner
+            .lock()
+            .unwrap()
+            .merge_from_file(&path, key)
+            .map(|r| r.into())
+            .map_err(|e| e.into())
+    }
+
+    /// Merge changes another program saved to the vault file
+    pub fn merge_external_changes(&self) -> Result<MergeReport, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .merge_external_changes()
+            .map(|r| r.into())
+            .map_err(|e| e.into())
+    }
+
+    /// Check if another program changed the vault file
+    pub fn has_external_changes(&self) -> Result<bool, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .has_external_changes()
+            .map_err(|e| e.into())
+    }
+
+    /// Create the advisory lock file other KeePass clients check
+    pub fn acquire_lock_file(&self, force: bool) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .acquire_lock_file(force)
+            .map_err(|e| e.into())
+    }
+
+    /// Remove the advisory lock file
+    pub fn release_lock_file(&self) {
+        self.inner.lock().unwrap().release_lock_file();
+    }
+
     /// Check if vault is locked
     pub fn is_locked(&self) -> bool {
         self.inner.lock().unwrap().is_locked()
     }
 
+    /// Set the auto-lock policy
+    pub fn set_auto_lock_policy(&self, policy: AutoLockPolicy) {
+        self.inner
+            .lock()
+            .unwrap()
+            .set_auto_lock_policy(policy.into());
+    }
+
+    /// Get the auto-lock policy
+    pub fn get_auto_lock_policy(&self) -> AutoLockPolicy {
+        self.inner.lock().unwrap().auto_lock_policy().clone().into()
+    }
+
+    /// Seconds left before the vault auto-locks (None without timeouts)
+    pub fn seconds_until_auto_lock(&self) -> Option<u64> {
+        self.inner
+            .lock()
+            .unwrap()
+            .time_until_auto_lock()
+            .map(|d| d.as_secs())
+    }
+
+    /// Lock the vault if the auto-lock policy has expired
+    pub fn check_auto_lock(&self) -> bool {
+        self.inner.lock().unwrap().check_auto_lock()
+    }
+
+    /// Notify the vault of a platform event, returns whether it is locked
+    pub fn handle_lock_trigger(&self, trigger: LockTrigger) -> bool {
+        self.inner
+            .lock()
+            .unwrap()
+            .handle_lock_trigger(trigger.into())
+    }
+
+    /// Get the database encryption settings
+    pub fn get_config(&self) -> Result<VaultConfig, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .config()
+            .map(|c| c.into())
+            .map_err(|e| e.into())
+    }
+
+    /// Change the database encryption settings (applied on next save)
+    pub fn set_config(&self, config: VaultConfig) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .set_config(config.into())
+            .map_err(|e| e.into())
+    }
+
     // Entry CRUD
     pub fn add_entry(&self, entry: Entry) -> Result<String, VaultError> {
         self.inner
@@ -281,6 +960,69 @@ impl Vault {
             .map_err(|e| e.into())
     }
 
+    /// Entry with field references and placeholders expanded, for display
+    pub fn get_resolved_entry(&self, id: String) -> Result<Entry, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .get_resolved_entry(&id)
+            .map(|e| e.into())
+            .map_err(|e| e.into())
+    }
+
+    /// Expand field references and placeholders in `text` for an entry
+    pub fn resolve_placeholders(&self, id: String, text: String) -> Result<String, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .resolve_placeholders(&id, &text)
+            .map_err(|e| e.into())
+    }
+
+    /// Record that an entry was used (updates access time and usage count)
+    pub fn record_access(&self, id: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .record_access(&id)
+            .map_err(|e| e.into())
+    }
+
+    /// Previous versions of an entry, oldest first
+    pub fn get_entry_history(&self, id: String) -> Result<Vec<Entry>, VaultError> {
+        let entries = self
+            .inner
+            .lock()
+            .unwrap()
+            .get_entry_history(&id)
+            .map_err(|e: CoreVaultError| -> VaultError { e.into() })?;
+        Ok(entries.into_iter().map(|e| e.into()).collect())
+    }
+
+    pub fn restore_entry_version(&self, id: String, index: u32) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .restore_entry_version(&id, index as usize)
+            .map_err(|e| e.into())
+    }
+
+    pub fn delete_history_item(&self, id: String, index: u32) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .delete_history_item(&id, index as usize)
+            .map_err(|e| e.into())
+    }
+
+    pub fn move_entry(&self, id: String, target_group: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .move_entry(&id, &target_group)
+            .map_err(|e| e.into())
+    }
+
     pub fn delete_entry(&self, id: String) -> Result<(), VaultError> {
         self.inner
             .lock()
@@ -323,6 +1065,14 @@ impl Vault {
             .map_err(|e| e.into())
     }
 
+    pub fn move_group(&self, id: String, target_parent: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .move_group(&id, &target_parent)
+            .map_err(|e| e.into())
+    }
+
     pub fn delete_group(&self, id: String) -> Result<(), VaultError> {
         self.inner
             .lock()
@@ -348,6 +1098,211 @@ impl Vault {
             .map_err(|e| e.into())
     }
 
+    pub fn restore_entry(&self, id: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .restore_entry(&id)
+            .map_err(|e| e.into())
+    }
+
+    pub fn restore_group(&self, id: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .restore_group(&id)
+            .map_err(|e| e.into())
+    }
+
+    // Deleted objects
+    pub fn get_deleted_objects(&self) -> Result<Vec<DeletedObject>, VaultError> {
+        let objects = self
+            .inner
+            .lock()
+            .unwrap()
+            .get_deleted_objects()
+            .map_err(|e: CoreVaultError| -> VaultError { e.into() })?;
+        Ok(objects.into_iter().map(|o| o.into()).collect())
+    }
+
+    /// Shrink the deleted objects list, returning the removed record count
+    pub fn compact_deleted_objects(&self, max_age_days: Option<u32>) -> Result<u64, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .compact_deleted_objects(max_age_days)
+            .map(|n| n as u64)
+            .map_err(|e| e.into())
+    }
+
+    /// Set the retention policy applied to the recycle bin on every save
+    pub fn set_recycle_bin_policy(&self, policy: RecycleBinPolicy) {
+        self.inner
+            .lock()
+            .unwrap()
+            .set_recycle_bin_policy(policy.into());
+    }
+
+    /// Get the recycle bin retention policy
+    pub fn get_recycle_bin_policy(&self) -> RecycleBinPolicy {
+        self.inner
+            .lock()
+            .unwrap()
+            .recycle_bin_policy()
+            .clone()
+            .into()
+    }
+
+    /// Apply the recycle bin retention policy now, returning the purged item count
+    pub fn purge_recycle_bin(&self) -> Result<u64, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .purge_recycle_bin()
+            .map(|n| n as u64)
+            .map_err(|e| e.into())
+    }
+
+    pub fn get_recycle_bin_items(&self) -> Result<Vec<RecycledItem>, VaultError> {
+        let items = self
+            .inner
+            .lock()
+            .unwrap()
+            .get_recycle_bin_items()
+            .map_err(|e: CoreVaultError| -> VaultError { e.into() })?;
+        Ok(items.into_iter().map(|i| i.into()).collect())
+    }
+
+    pub fn is_recycle_bin_enabled(&self) -> Result<bool, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .is_recycle_bin_enabled()
+            .map_err(|e| e.into())
+    }
+
+    pub fn set_recycle_bin_enabled(&self, enabled: bool) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .set_recycle_bin_enabled(enabled)
+            .map_err(|e| e.into())
+    }
+
+    pub fn recycle_bin_id(&self) -> Result<Option<String>, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .recycle_bin_id()
+            .map_err(|e| e.into())
+    }
+
+    // Attachments
+    pub fn get_attachments(&self, entry_id: String) -> Result<Vec<Attachment>, VaultError> {
+        let attachments = self
+            .inner
+            .lock()
+            .unwrap()
+            .get_attachments(&entry_id)
+            .map_err(|e: CoreVaultError| -> VaultError { e.into() })?;
+        Ok(attachments.into_iter().map(|a| a.into()).collect())
+    }
+
+    /// Read an attachment's content, e.g. to export it to a file
+    pub fn get_attachment(&self, entry_id: String, name: String) -> Result<Vec<u8>, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .get_attachment(&entry_id, &name)
+            .map_err(|e| e.into())
+    }
+
+    pub fn add_attachment(
+        &self,
+        entry_id: String,
+        name: String,
+        data: Vec<u8>,
+        protected: bool,
+    ) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .add_attachment(&entry_id, &name, data, protected)
+            .map_err(|e| e.into())
+    }
+
+    pub fn rename_attachment(
+        &self,
+        entry_id: String,
+        name: String,
+        new_name: String,
+    ) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .rename_attachment(&entry_id, &name, &new_name)
+            .map_err(|e| e.into())
+    }
+
+    pub fn delete_attachment(&self, entry_id: String, name: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .delete_attachment(&entry_id, &name)
+            .map_err(|e| e.into())
+    }
+
+    // Custom icons
+    pub fn get_custom_icons(&self) -> Result<Vec<CustomIcon>, VaultError> {
+        let icons = self
+            .inner
+            .lock()
+            .unwrap()
+            .get_custom_icons()
+            .map_err(|e: CoreVaultError| -> VaultError { e.into() })?;
+        Ok(icons.into_iter().map(|i| i.into()).collect())
+    }
+
+    pub fn add_custom_icon(&self, data: Vec<u8>) -> Result<String, VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .add_custom_icon(data)
+            .map_err(|e| e.into())
+    }
+
+    pub fn remove_custom_icon(&self, id: String) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .remove_custom_icon(&id)
+            .map_err(|e| e.into())
+    }
+
+    pub fn set_entry_custom_icon(
+        &self,
+        id: String,
+        icon_id: Option<String>,
+    ) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .set_entry_custom_icon(&id, icon_id.as_deref())
+            .map_err(|e| e.into())
+    }
+
+    pub fn set_group_custom_icon(
+        &self,
+        id: String,
+        icon_id: Option<String>,
+    ) -> Result<(), VaultError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .set_group_custom_icon(&id, icon_id.as_deref())
+            .map_err(|e| e.into())
+    }
+
     // Search
     pub fn search_entries(&self, query: String) -> Result<Vec<Entry>, VaultError> {
         let entries = self
@@ -375,6 +1330,17 @@ impl Vault {
             .collect())
     }
 
+    /// List all tags with the number of entries using them
+    pub fn get_tags(&self) -> Result<Vec<TagCount>, VaultError> {
+        let tags = self
+            .inner
+            .lock()
+            .unwrap()
+            .get_tags()
+            .map_err(|e: CoreVaultError| -> VaultError { e.into() })?;
+        Ok(tags.into_iter().map(|t| t.into()).collect())
+    }
+
     pub fn get_favorites(&self) -> Result<Vec<Entry>, VaultError> {
         let entries = self
             .inner
@@ -399,7 +1365,7 @@ pub fn create_vault(
     password: String,
     config: VaultConfig,
 ) -> Result<Arc<Vault>, VaultError> {
-    let core_vault = CoreVault::create(&PathBuf::from(path), &password, config.into())
+    let core_vault = CoreVault::create(PathBuf::from(path), &password, config.into())
         .map_err(|e| -> VaultError { e.into() })?;
     Ok(Arc::new(Vault {
         inner: Mutex::new(core_vault),
@@ -409,12 +1375,96 @@ pub fn create_vault(
 #[uniffi::export]
 pub fn open_vault(path: String, password: String) -> Result<Arc<Vault>, VaultError> {
     let core_vault =
-        CoreVault::open(&PathBuf::from(path), &password).map_err(|e| -> VaultError { e.into() })?;
+        CoreVault::open(PathBuf::from(path), &password).map_err(|e| -> VaultError { e.into() })?;
+    Ok(Arc::new(Vault {
+        inner: Mutex::new(core_vault),
+    }))
+}
+
+#[uniffi::export]
+pub fn create_vault_with_credentials(
+    path: String,
+    credentials: Credentials,
+    config: VaultConfig,
+) -> Result<Arc<Vault>, VaultError> {
+    let key: CompositeKey = credentials.try_into()?;
+    let core_vault = CoreVault::create_with_key(PathBuf::from(path), key, config.into())
+        .map_err(|e| -> VaultError { e.into() })?;
+    Ok(Arc::new(Vault {
+        inner: Mutex::new(core_vault),
+    }))
+}
+
+#[uniffi::export]
+pub fn open_vault_with_credentials(
+    path: String,
+    credentials: Credentials,
+) -> Result<Arc<Vault>, VaultError> {
+    let key: CompositeKey = credentials.try_into()?;
+    let core_vault = CoreVault::open_with_key(PathBuf::from(path), key)
+        .map_err(|e| -> VaultError { e.into() })?;
+    Ok(Arc::new(Vault {
+        inner: Mutex::new(core_vault),
+    }))
+}
+
+/// Create a vault that only lives in memory
+#[uniffi::export]
+pub fn create_vault_in_memory(
+    credentials: Credentials,
+    config: VaultConfig,
+) -> Result<Arc<Vault>, VaultError> {
+    let key: CompositeKey = credentials.try_into()?;
+    let core_vault =
+        CoreVault::create_in_memory(key, config.into()).map_err(|e| -> VaultError { e.into() })?;
+    Ok(Arc::new(Vault {
+        inner: Mutex::new(core_vault),
+    }))
+}
+
+#[uniffi::export]
+pub fn open_vault_from_bytes(
+    data: Vec<u8>,
+    credentials: Credentials,
+) -> Result<Arc<Vault>, VaultError> {
+    let key: CompositeKey = credentials.try_into()?;
+    let core_vault =
+        CoreVault::open_from_bytes(&data, key).map_err(|e| -> VaultError { e.into() })?;
+    Ok(Arc::new(Vault {
+        inner: Mutex::new(core_vault),
+    }))
+}
+
+#[cfg(feature = "webdav")]
+#[uniffi::export]
+pub fn open_vault_webdav(
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+    allow_insecure_auth: bool,
+    credentials: Credentials,
+) -> Result<Arc<Vault>, VaultError> {
+    let key: CompositeKey = credentials.try_into()?;
+    let mut storage = crate::WebDavStorage::new(&url);
+    if let Some(username) = username {
+        storage = storage.with_credentials(&username, password.as_deref().unwrap_or_default());
+    }
+    if allow_insecure_auth {
+        storage = storage.allow_insecure_auth();
+    }
+    let core_vault =
+        CoreVault::open_with_storage(storage, key).map_err(|e| -> VaultError { e.into() })?;
     Ok(Arc::new(Vault {
         inner: Mutex::new(core_vault),
     }))
 }
 
+/// Write a new KeePass 2.0 XML key file
+#[uniffi::export]
+pub fn generate_key_file(path: String) -> Result<(), VaultError> {
+    crate::key::create_key_file(PathBuf::from(path)).map_err(|e| e.into())
+}
+
 // ============================================================================
 // TOTP Functions
 // ============================================================================
@@ -1,130 +1,640 @@
+use crate::attachment::{self, Attachment};
+use crate::auto_lock::{AutoLockPolicy, LockTrigger};
+pub use crate::config::VaultConfig;
+use crate::deleted_objects::{self, DeletedObject};
 use crate::entry::Entry;
 use crate::error::{Result, VaultError};
 use crate::group::Group;
-use std::fs::File;
-use std::path::{Path, PathBuf};
-
-/// Vault configuration
-#[derive(Debug, Clone)]
-pub struct VaultConfig {
-    /// KDF iterations (Argon2 or AES-KDF)
-    pub kdf_iterations: u64,
-
-    /// Argon2 memory (in KB)
-    pub argon2_memory: u64,
-
-    /// Argon2 parallelism
-    pub argon2_parallelism: u32,
-}
-
-impl Default for VaultConfig {
-    fn default() -> Self {
-        Self {
-            kdf_iterations: 2,
-            argon2_memory: 64 * 1024, // 64 MB
-            argon2_parallelism: 2,
-        }
-    }
+use crate::history;
+use crate::icon::{self, CustomIcon};
+use crate::key::CompositeKey;
+use crate::lock_file::LockFile;
+use crate::merge::{self, MergeReport};
+use crate::metadata::{self, VaultMetadata};
+use crate::quick_unlock::{QuickUnlock, QuickUnlockConfig};
+use crate::recycle_bin::{self, RecycleBinPolicy, RecycledItem};
+use crate::resolver::Resolver;
+use crate::search::{self, TagCount};
+use crate::storage::{self, BackupPolicy, LocalFileStorage, Revision, VaultStorage};
+use std::cell::Cell;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
+
+/// Entry CustomData key marking favorites
+///
+/// KeePassXC keeps unknown CustomData items, so the flag survives editing
+/// the vault in other clients.
+pub const FAVORITE_KEY: &str = "KeedaVault.Favorite";
+
+/// Entry and group CustomData key holding the group an item was moved out of
+///
+/// KDBX 4.1 has a `PreviousParentGroup` element for this, which the keepass
+/// crate does not support yet.
+pub const PREVIOUS_PARENT_KEY: &str = "KeedaVault.PreviousParentGroup";
+
+/// Where the encrypted database lives
+enum Source {
+    /// A storage backend, with the revision last loaded or saved
+    Storage {
+        storage: Box<dyn VaultStorage>,
+        revision: Revision,
+    },
+    /// Memory only, with the last loaded or saved encrypted bytes
+    Memory { data: Vec<u8> },
 }
 
 /// Main vault structure
 pub struct Vault {
-    path: PathBuf,
+    source: Source,
     database: Option<keepass::Database>,
-    password: String, // Store password for saving
+    key: CompositeKey, // Store composite key for saving
+    quick_unlock: Option<QuickUnlock>,
+    backup_policy: Option<BackupPolicy>,
+    recycle_bin_policy: RecycleBinPolicy,
+    lock_file: Option<LockFile>,
     is_locked: bool,
+    auto_lock: AutoLockPolicy,
+    unlocked_at: Instant,
+    last_activity: Cell<Instant>,
 }
 
 impl Vault {
     /// Open an existing vault
     pub fn open<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
-        let path = path.as_ref().to_path_buf();
+        Self::open_with_key(path, CompositeKey::from_password(password))
+    }
+
+    /// Open an existing vault with a composite key (password and/or key file)
+    pub fn open_with_key<P: AsRef<Path>>(path: P, key: CompositeKey) -> Result<Self> {
+        Self::open_with_storage(LocalFileStorage::new(path), key)
+    }
 
-        // Read the file
-        let mut file = File::open(&path)
-            .map_err(|e| VaultError::OpenError(format!("Failed to open file: {}", e)))?;
+    /// Open an existing vault from a storage backend
+    pub fn open_with_storage<S: VaultStorage + 'static>(
+        mut storage: S,
+        key: CompositeKey,
+    ) -> Result<Self> {
+        // Remember the revision to detect external changes
+        let (data, revision) = storage.load()?;
 
         // Open the database using keepass 0.8 API
-        let database = keepass::Database::open(
-            &mut file,
-            keepass::DatabaseKey::new().with_password(password),
-        )?;
+        let database = keepass::Database::open(&mut data.as_slice(), key.to_database_key())?;
+
+        Ok(Self::with_database(
+            database,
+            key,
+            Source::Storage {
+                storage: Box::new(storage),
+                revision,
+            },
+        ))
+    }
 
-        Ok(Self {
-            path,
-            database: Some(database),
-            password: password.to_string(),
-            is_locked: false,
-        })
+    /// Open a vault from an encrypted stream, keeping it in memory only
+    ///
+    /// The vault has no file: use `save_to_writer()`, `save_to_bytes()` or
+    /// `save_as()` to persist it.
+    pub fn open_from_reader<R: Read>(mut reader: R, key: CompositeKey) -> Result<Self> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| VaultError::OpenError(format!("Failed to read vault: {}", e)))?;
+
+        let database = keepass::Database::open(&mut data.as_slice(), key.to_database_key())?;
+
+        Ok(Self::with_database(database, key, Source::Memory { data }))
+    }
+
+    /// Open a vault from encrypted bytes, keeping it in memory only
+    pub fn open_from_bytes(data: &[u8], key: CompositeKey) -> Result<Self> {
+        Self::open_from_reader(data, key)
     }
 
     /// Create a new vault
-    pub fn create<P: AsRef<Path>>(path: P, password: &str, _config: VaultConfig) -> Result<Self> {
-        let path = path.as_ref().to_path_buf();
+    pub fn create<P: AsRef<Path>>(path: P, password: &str, config: VaultConfig) -> Result<Self> {
+        Self::create_with_key(path, CompositeKey::from_password(password), config)
+    }
+
+    /// Create a new vault protected by a composite key (password and/or key file)
+    ///
+    /// Fails if a file already exists at `path`.
+    pub fn create_with_key<P: AsRef<Path>>(
+        path: P,
+        key: CompositeKey,
+        config: VaultConfig,
+    ) -> Result<Self> {
+        Self::create_with_storage(LocalFileStorage::new(path), key, config, false)
+    }
+
+    /// Create a new vault in a storage backend
+    ///
+    /// Fails if the storage already holds data, unless `overwrite` is set.
+    pub fn create_with_storage<S: VaultStorage + 'static>(
+        mut storage: S,
+        key: CompositeKey,
+        config: VaultConfig,
+        overwrite: bool,
+    ) -> Result<Self> {
+        if !overwrite && storage.revision()?.is_some() {
+            return Err(VaultError::SaveError(format!(
+                "{} already exists",
+                storage.location()
+            )));
+        }
+
+        let (database, data) = Self::new_database(&key, config)?;
+
+        // Save immediately
+        let revision = storage.save(&data, None)?;
+
+        Ok(Self::with_database(
+            database,
+            key,
+            Source::Storage {
+                storage: Box::new(storage),
+                revision,
+            },
+        ))
+    }
+
+    /// Create a new vault that only lives in memory
+    pub fn create_in_memory(key: CompositeKey, config: VaultConfig) -> Result<Self> {
+        let (database, data) = Self::new_database(&key, config)?;
+
+        Ok(Self::with_database(database, key, Source::Memory { data }))
+    }
 
-        // Create a new database with default config
-        let mut database = keepass::Database::new(keepass::config::DatabaseConfig::default());
+    /// Build an empty database and encrypt it once
+    fn new_database(
+        key: &CompositeKey,
+        config: VaultConfig,
+    ) -> Result<(keepass::Database, Vec<u8>)> {
+        if key.is_empty() {
+            return Err(VaultError::InvalidPassword);
+        }
+
+        // Create a new database with the requested KDF and cipher settings
+        let db_config = config.to_database_config(&keepass::config::DatabaseConfig::default())?;
+        let mut database = keepass::Database::new(db_config);
 
         // Set up the root group
         database.root.name = "Root".to_string();
 
-        // Save immediately
-        let mut file = File::create(&path)
-            .map_err(|e| VaultError::SaveError(format!("Failed to create file: {}", e)))?;
+        // Same history limits as KeePass
+        database.meta.history_max_items = Some(history::DEFAULT_HISTORY_MAX_ITEMS);
+        database.meta.history_max_size = Some(history::DEFAULT_HISTORY_MAX_SIZE);
+
+        let mut data = Vec::new();
+        database.save(&mut data, key.to_database_key())?;
 
-        database.save(
-            &mut file,
-            keepass::DatabaseKey::new().with_password(password),
-        )?;
+        Ok((database, data))
+    }
 
-        Ok(Self {
-            path,
+    fn with_database(database: keepass::Database, key: CompositeKey, source: Source) -> Self {
+        Self {
+            source,
             database: Some(database),
-            password: password.to_string(),
+            key,
+            quick_unlock: None,
+            backup_policy: None,
+            recycle_bin_policy: RecycleBinPolicy::default(),
+            lock_file: None,
             is_locked: false,
-        })
+            auto_lock: AutoLockPolicy::default(),
+            unlocked_at: Instant::now(),
+            last_activity: Cell::new(Instant::now()),
+        }
     }
 
     /// Save the vault
+    ///
+    /// The database is written to a temporary file and atomically renamed over
+    /// the vault, after backing up the previous version if a backup policy is set.
+    ///
+    /// Returns `VaultError::ExternalModification` if another program changed
+    /// the file since it was opened or last saved, see `force_save()`.
+    /// In-memory vaults have no file and return `VaultError::SaveError`.
     pub fn save(&mut self) -> Result<()> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
+        self.write(false)
+    }
+
+    /// Save the vault, overwriting changes made by other programs
+    pub fn force_save(&mut self) -> Result<()> {
+        self.write(true)
+    }
+
+    /// Save the vault to a new file, which becomes the vault's file
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.save_to_storage(LocalFileStorage::new(path))
+    }
+
+    /// Save the vault to a storage backend, which becomes the vault's storage
+    ///
+    /// Replaces any data stored there.
+    pub fn save_to_storage<S: VaultStorage + 'static>(&mut self, mut storage: S) -> Result<()> {
+        let data = self.encrypt()?;
+
+        let revision = storage.save(&data, None)?;
+        if self.path() != storage.path() {
+            self.lock_file = None;
         }
+        self.source = Source::Storage {
+            storage: Box::new(storage),
+            revision,
+        };
 
-        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        Ok(())
+    }
+
+    /// Write the encrypted vault to a stream
+    ///
+    /// For in-memory vaults the written bytes are also what `unlock()` decrypts.
+    pub fn save_to_writer<W: Write>(&mut self, mut writer: W) -> Result<()> {
+        let data = self.encrypt()?;
+
+        writer
+            .write_all(&data)
+            .and_then(|_| writer.flush())
+            .map_err(|e| VaultError::SaveError(format!("Failed to write vault: {}", e)))?;
+
+        if let Source::Memory { data: snapshot } = &mut self.source {
+            *snapshot = data;
+        }
+
+        Ok(())
+    }
+
+    /// Encrypt the vault into a byte buffer
+    pub fn save_to_bytes(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.save_to_writer(&mut data)?;
+        Ok(data)
+    }
+
+    /// Check if another program changed the stored vault since it was opened or last saved
+    ///
+    /// Always false for in-memory vaults.
+    pub fn has_external_changes(&self) -> Result<bool> {
+        match &self.source {
+            Source::Storage { storage, revision } => storage.has_changed(revision),
+            Source::Memory { .. } => Ok(false),
+        }
+    }
+
+    /// Create an advisory lock file telling other KeePass clients the vault is open
+    ///
+    /// Fails with `VaultError::VaultInUse` if another client holds the lock,
+    /// unless `force` is set. The lock file is removed by `release_lock_file()`
+    /// or when the vault is dropped. Does nothing unless the vault is stored
+    /// in a local file.
+    pub fn acquire_lock_file(&mut self, force: bool) -> Result<()> {
+        if let Source::Storage { storage, .. } = &self.source {
+            if let Some(path) = storage.path() {
+                if self.lock_file.is_none() {
+                    self.lock_file = Some(LockFile::acquire(path, force)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the advisory lock file, if held
+    pub fn release_lock_file(&mut self) {
+        self.lock_file = None;
+    }
+
+    /// Check if the vault holds its advisory lock file
+    pub fn holds_lock_file(&self) -> bool {
+        self.lock_file.is_some()
+    }
+
+    fn write(&mut self, force: bool) -> Result<()> {
+        self.check_access_mut()?;
+
+        if matches!(self.source, Source::Memory { .. }) {
+            return Err(VaultError::SaveError(
+                "In-memory vault has no file, use save_as()".to_string(),
+            ));
+        }
+
+        // Encrypt fully in memory before touching the storage
+        let data = self.encrypt()?;
+
+        let Source::Storage { storage, revision } = &mut self.source else {
+            unreachable!("checked above");
+        };
+
+        if !force && storage.has_changed(revision)? {
+            return Err(VaultError::ExternalModification(storage.location()));
+        }
+
+        if let (Some(policy), Some(path)) = (&self.backup_policy, storage.path()) {
+            storage::backup_file(path, policy)?;
+        }
+
+        // The backend re-checks the revision while saving where it can
+        *revision = storage.save(&data, (!force).then_some(&*revision))?;
+
+        Ok(())
+    }
+
+    fn encrypt(&mut self) -> Result<Vec<u8>> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        recycle_bin::purge(
+            database,
+            &self.recycle_bin_policy,
+            keepass::db::Times::now(),
+        );
+        icon::remove_unused(database);
+        attachment::remove_unused(database);
+
+        let mut data = Vec::new();
+        database.save(&mut data, self.key.to_database_key())?;
+
+        Ok(data)
+    }
+
+    /// Set the rotating backup policy used by `save()` (None disables backups)
+    pub fn set_backup_policy(&mut self, policy: Option<BackupPolicy>) {
+        self.backup_policy = policy;
+    }
+
+    /// Get the rotating backup policy
+    pub fn backup_policy(&self) -> Option<&BackupPolicy> {
+        self.backup_policy.as_ref()
+    }
+
+    /// Merge another vault into this one (KeePass-style synchronization)
+    ///
+    /// Entries and groups are matched by UUID and the newer version wins,
+    /// with the older entry version kept in history. Deletions and moves made
+    /// in either vault are applied. The result is written on the next `save()`.
+    pub fn merge_from(&mut self, other: &Vault) -> Result<MergeReport> {
+        self.check_access_mut()?;
+        other.check_access()?;
+
+        let source = other.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+
+        Ok(merge::merge(database, source))
+    }
+
+    /// Merge the vault file at `path` (e.g. a sync conflict copy) into this one
+    pub fn merge_from_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        key: CompositeKey,
+    ) -> Result<MergeReport> {
+        let other = Vault::open_with_key(path, key)?;
+        self.merge_from(&other)
+    }
+
+    /// Merge changes another program saved to this vault's file
+    ///
+    /// Afterwards `save()` no longer reports `VaultError::ExternalModification`
+    /// for the merged changes. Does nothing for in-memory vaults.
+    pub fn merge_external_changes(&mut self) -> Result<MergeReport> {
+        self.check_access_mut()?;
+
+        let Source::Storage { storage, revision } = &mut self.source else {
+            return Ok(MergeReport::default());
+        };
+
+        let (data, current) = storage.load()?;
+        let source = keepass::Database::open(&mut data.as_slice(), self.key.to_database_key())?;
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+
+        let report = merge::merge(database, &source);
+        *revision = current;
+
+        Ok(report)
+    }
 
-        let mut file = File::create(&self.path)
-            .map_err(|e| VaultError::SaveError(format!("Failed to open file: {}", e)))?;
+    /// Change the master credentials
+    ///
+    /// `current` must match the key the vault was opened with. The new key is
+    /// used from the next `save()` on.
+    pub fn change_credentials(
+        &mut self,
+        current: &CompositeKey,
+        new_key: CompositeKey,
+    ) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+
+        if *current != self.key || new_key.is_empty() {
+            return Err(VaultError::InvalidPassword);
+        }
+
+        self.key = new_key;
+        database.meta.master_key_changed = Some(keepass::db::Times::now());
 
-        database.save(
-            &mut file,
-            keepass::DatabaseKey::new().with_password(&self.password),
-        )?;
+        // The sealed key is stale now, a new PIN must be set up
+        self.quick_unlock = None;
 
         Ok(())
     }
 
+    /// Get the database name, color and other database-wide settings
+    pub fn get_metadata(&self) -> Result<VaultMetadata> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        Ok(VaultMetadata::from(&database.meta))
+    }
+
+    /// Change the database-wide settings, written on the next `save()`
+    ///
+    /// Returns `VaultError::InvalidConfig` if the color is not `#RRGGBB`.
+    pub fn set_metadata(&mut self, metadata: &VaultMetadata) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        metadata::apply(&mut database.meta, metadata)
+    }
+
     /// Lock the vault
+    ///
+    /// Drops the decrypted database and zeroizes the cached composite key.
     pub fn lock(&mut self) {
         self.database = None;
+        self.key.zeroize();
         self.is_locked = true;
     }
 
-    /// Check if the vault is locked
+    /// Unlock a locked vault by re-reading and decrypting the stored vault
+    ///
+    /// In-memory vaults decrypt the last loaded or saved bytes instead.
+    /// Returns `VaultError::InvalidPassword` if the key does not match.
+    /// Does nothing if the vault is already unlocked, so unsaved changes
+    /// are kept.
+    pub fn unlock(&mut self, key: CompositeKey) -> Result<()> {
+        if !self.check_auto_lock() {
+            return if key == self.key {
+                Ok(())
+            } else {
+                Err(VaultError::InvalidPassword)
+            };
+        }
+
+        let database = match &mut self.source {
+            Source::Storage { storage, revision } => {
+                let (data, current) = storage.load()?;
+                let database =
+                    keepass::Database::open(&mut data.as_slice(), key.to_database_key())?;
+                *revision = current;
+                database
+            }
+            Source::Memory { data } => {
+                keepass::Database::open(&mut data.as_slice(), key.to_database_key())?
+            }
+        };
+
+        self.database = Some(database);
+        self.key = key;
+        self.is_locked = false;
+        self.unlocked_at = Instant::now();
+        self.last_activity.set(self.unlocked_at);
+
+        Ok(())
+    }
+
+    /// Enable quick unlock with a short PIN
+    ///
+    /// The current composite key is sealed under a key derived from `pin` and
+    /// kept in memory only, so `quick_unlock` can reopen the vault after
+    /// `lock()`. After `config.max_attempts` wrong PINs the sealed key is
+    /// destroyed and a full unlock is required.
+    pub fn enable_quick_unlock(&mut self, pin: &str, config: QuickUnlockConfig) -> Result<()> {
+        self.check_access_mut()?;
+
+        self.quick_unlock = Some(QuickUnlock::seal(pin, &self.key, config)?);
+
+        Ok(())
+    }
+
+    /// Disable quick unlock and destroy the sealed key
+    pub fn disable_quick_unlock(&mut self) {
+        self.quick_unlock = None;
+    }
+
+    /// Check if quick unlock is available
+    pub fn is_quick_unlock_enabled(&self) -> bool {
+        self.quick_unlock.is_some()
+    }
+
+    /// Remaining wrong PIN attempts, if quick unlock is enabled
+    pub fn quick_unlock_attempts_remaining(&self) -> Option<u32> {
+        self.quick_unlock.as_ref().map(|q| q.remaining_attempts())
+    }
+
+    /// Unlock a locked vault with the quick unlock PIN
+    pub fn quick_unlock(&mut self, pin: &str) -> Result<()> {
+        let sealed = self
+            .quick_unlock
+            .as_mut()
+            .ok_or(VaultError::QuickUnlockUnavailable)?;
+
+        let result = sealed.open(pin);
+        if sealed.remaining_attempts() == 0 {
+            self.quick_unlock = None;
+        }
+
+        self.unlock(result?)
+    }
+
+    /// Check if the vault is locked (or its auto-lock policy has expired)
     pub fn is_locked(&self) -> bool {
+        self.is_locked || self.auto_lock_expired()
+    }
+
+    /// Set the auto-lock policy
+    ///
+    /// Timers restart from now.
+    pub fn set_auto_lock_policy(&mut self, policy: AutoLockPolicy) {
+        self.auto_lock = policy;
+        self.unlocked_at = Instant::now();
+        self.last_activity.set(self.unlocked_at);
+    }
+
+    /// Get the auto-lock policy
+    pub fn auto_lock_policy(&self) -> &AutoLockPolicy {
+        &self.auto_lock
+    }
+
+    /// Time left before the vault auto-locks
+    ///
+    /// Returns `None` when the vault is locked or the policy has no timeouts.
+    pub fn time_until_auto_lock(&self) -> Option<Duration> {
+        if self.is_locked {
+            return None;
+        }
+
+        self.auto_lock
+            .remaining(self.unlocked_at, self.last_activity.get(), Instant::now())
+    }
+
+    /// Lock the vault if the auto-lock policy has expired
+    ///
+    /// Frontends should call this from a timer so the decrypted database is
+    /// dropped even while the vault is idle. Returns whether the vault is locked.
+    pub fn check_auto_lock(&mut self) -> bool {
+        if !self.is_locked && self.auto_lock_expired() {
+            self.lock();
+        }
         self.is_locked
     }
 
-    /// Get the vault path
-    pub fn path(&self) -> &Path {
-        &self.path
+    /// Notify the vault of a platform event
+    ///
+    /// Locks the vault if the policy lists the trigger. Returns whether the
+    /// vault is locked.
+    pub fn handle_lock_trigger(&mut self, trigger: LockTrigger) -> bool {
+        if !self.is_locked && self.auto_lock.locks_on(trigger) {
+            self.lock();
+        }
+        self.check_auto_lock()
+    }
+
+    /// Get the vault path (None for in-memory vaults)
+    pub fn path(&self) -> Option<&Path> {
+        match &self.source {
+            Source::Storage { storage, .. } => storage.path(),
+            Source::Memory { .. } => None,
+        }
+    }
+
+    /// Get the database encryption settings
+    pub fn config(&self) -> Result<VaultConfig> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+
+        Ok(VaultConfig::from(&database.config))
+    }
+
+    /// Change the database encryption settings
+    ///
+    /// The new KDF and cipher settings take effect on the next `save()`.
+    pub fn set_config(&mut self, config: VaultConfig) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+
+        database.config = config.to_database_config(&database.config)?;
+        database.meta.settings_changed = Some(keepass::db::Times::now());
+
+        Ok(())
     }
 
     /// Get all entries from the database
     pub fn get_entries(&self) -> Result<Vec<Entry>> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access()?;
 
         let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
         let mut entries = Vec::new();
@@ -137,9 +647,7 @@ impl Vault {
 
     /// Get a specific entry by ID
     pub fn get_entry(&self, id: &str) -> Result<Entry> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access()?;
 
         let entries = self.get_entries()?;
         entries
@@ -148,13 +656,50 @@ impl Vault {
             .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))
     }
 
+    /// Get an entry with field references and placeholders expanded
+    ///
+    /// Use this for display and copying; edit the entry from `get_entry` so
+    /// the references are kept.
+    pub fn get_resolved_entry(&self, id: &str) -> Result<Entry> {
+        let entries = self.get_entries()?;
+        let entry = entries
+            .iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+
+        Ok(Resolver::new(&entries).resolve_entry(entry))
+    }
+
+    /// Expand field references and placeholders in `text` for an entry
+    pub fn resolve_placeholders(&self, id: &str, text: &str) -> Result<String> {
+        let entries = self.get_entries()?;
+        let entry = entries
+            .iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+
+        Ok(Resolver::new(&entries).resolve(entry, text))
+    }
+
+    /// List all tags with the number of entries using them
+    ///
+    /// Entries in the recycle bin are not counted.
+    pub fn get_tags(&self) -> Result<Vec<TagCount>> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        let mut entries = self.get_entries()?;
+        entries.retain(|e| !recycle_bin::contains(database, &e.id));
+
+        Ok(search::list_tags(&entries))
+    }
+
     /// Add a new entry to a specific group
     pub fn add_entry(&mut self, entry: Entry) -> Result<String> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access_mut()?;
 
         let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let custom_icon = Self::resolve_custom_icon(database, entry.custom_icon_id.as_deref())?;
 
         // Find the target group
         let group = Self::find_group_mut(&mut database.root, &entry.group_id)
@@ -204,6 +749,24 @@ impl Vault {
             );
         }
 
+        kp_entry.tags = Self::normalize_tags(&entry.tags);
+        Self::set_favorite(&mut kp_entry, entry.is_favorite);
+        kp_entry.custom_icon_uuid = custom_icon;
+
+        // Keep the entry's own timestamps
+        kp_entry.times = keepass::db::Times::new();
+        kp_entry
+            .times
+            .set_creation(Self::kdbx_time(entry.created_at));
+        kp_entry
+            .times
+            .set_last_modification(Self::kdbx_time(entry.modified_at));
+        kp_entry
+            .times
+            .set_last_access(Self::kdbx_time(entry.accessed_at));
+        kp_entry.times.usage_count = entry.usage_count as usize;
+        Self::set_expiry(&mut kp_entry.times, entry.expires_at);
+
         // Add custom fields
         for field in &entry.custom_fields {
             let value = if field.protected {
@@ -221,103 +784,347 @@ impl Vault {
     }
 
     /// Update an existing entry
+    ///
+    /// If anything changed, the previous version is added to the entry history.
+    /// `entry.group_id` is ignored, use `move_entry` to change the group.
     pub fn update_entry(&mut self, id: &str, entry: Entry) -> Result<()> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
-
-        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        self.check_access_mut()?;
 
-        // Find and update the entry
-        let kp_entry = Self::find_entry_mut(&mut database.root, id)
-            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        let custom_icon = Self::resolve_custom_icon(database, entry.custom_icon_id.as_deref())?;
 
-        // Update standard fields
-        kp_entry.fields.insert(
-            "Title".to_string(),
-            keepass::db::Value::Unprotected(entry.title.clone()),
-        );
-        kp_entry.fields.insert(
-            "UserName".to_string(),
-            keepass::db::Value::Unprotected(entry.username.clone()),
-        );
-        kp_entry.fields.insert(
-            "Password".to_string(),
-            keepass::db::Value::Protected(entry.password.as_bytes().to_vec().into()),
-        );
-        kp_entry.fields.insert(
-            "URL".to_string(),
-            keepass::db::Value::Unprotected(entry.url.clone()),
-        );
-
-        if !entry.notes.is_empty() {
+        self.change_entry(id, |kp_entry, _| {
+            // Update standard fields
             kp_entry.fields.insert(
-                "Notes".to_string(),
-                keepass::db::Value::Unprotected(entry.notes.clone()),
+                "Title".to_string(),
+                keepass::db::Value::Unprotected(entry.title.clone()),
             );
-        } else {
-            kp_entry.fields.remove("Notes");
-        }
-
-        // Update TOTP
-        if let Some(totp_secret) = &entry.totp_secret {
             kp_entry.fields.insert(
-                "otp".to_string(),
-                keepass::db::Value::Protected(totp_secret.as_bytes().to_vec().into()),
+                "UserName".to_string(),
+                keepass::db::Value::Unprotected(entry.username.clone()),
+            );
+            kp_entry.fields.insert(
+                "Password".to_string(),
+                keepass::db::Value::Protected(entry.password.as_bytes().to_vec().into()),
+            );
+            kp_entry.fields.insert(
+                "URL".to_string(),
+                keepass::db::Value::Unprotected(entry.url.clone()),
             );
-        } else {
-            kp_entry.fields.remove("otp");
-        }
 
-        // Update custom fields - remove old ones and add new ones
-        let standard_fields = ["Title", "UserName", "Password", "URL", "Notes", "otp"];
-        kp_entry
-            .fields
-            .retain(|k, _| standard_fields.contains(&k.as_str()));
+            if !entry.notes.is_empty() {
+                kp_entry.fields.insert(
+                    "Notes".to_string(),
+                    keepass::db::Value::Unprotected(entry.notes.clone()),
+                );
+            } else {
+                kp_entry.fields.remove("Notes");
+            }
 
-        for field in &entry.custom_fields {
-            let value = if field.protected {
-                keepass::db::Value::Protected(field.value.as_bytes().to_vec().into())
+            // Update TOTP
+            if let Some(totp_secret) = &entry.totp_secret {
+                kp_entry.fields.insert(
+                    "otp".to_string(),
+                    keepass::db::Value::Protected(totp_secret.as_bytes().to_vec().into()),
+                );
             } else {
-                keepass::db::Value::Unprotected(field.value.clone())
-            };
-            kp_entry.fields.insert(field.key.clone(), value);
+                kp_entry.fields.remove("otp");
+            }
+
+            kp_entry.tags = Self::normalize_tags(&entry.tags);
+            Self::set_expiry(&mut kp_entry.times, entry.expires_at);
+            Self::set_favorite(kp_entry, entry.is_favorite);
+            kp_entry.custom_icon_uuid = custom_icon;
+
+            // Update custom fields - remove old ones and add new ones
+            let standard_fields = ["Title", "UserName", "Password", "URL", "Notes", "otp"];
+            kp_entry
+                .fields
+                .retain(|k, _| standard_fields.contains(&k.as_str()));
+
+            for field in &entry.custom_fields {
+                let value = if field.protected {
+                    keepass::db::Value::Protected(field.value.as_bytes().to_vec().into())
+                } else {
+                    keepass::db::Value::Unprotected(field.value.clone())
+                };
+                kp_entry.fields.insert(field.key.clone(), value);
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Apply `change` to an entry, keeping the previous version in its history
+    ///
+    /// The modification time is only bumped, and a version only kept, if the
+    /// entry actually changed.
+    fn change_entry<T>(
+        &mut self,
+        id: &str,
+        change: impl FnOnce(
+            &mut keepass::db::Entry,
+            &mut Vec<keepass::db::HeaderAttachment>,
+        ) -> Result<T>,
+    ) -> Result<T> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let kp_entry = Self::find_entry_mut(&mut database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+        let kept_history = kp_entry.history.take();
+        let previous = kp_entry.clone();
+
+        let result = change(kp_entry, &mut database.header_attachments);
+
+        let changed = *kp_entry != previous;
+        kp_entry.history = kept_history;
+        if changed {
+            let now = keepass::db::Times::now();
+            kp_entry.times.set_last_modification(now);
+            kp_entry.times.set_last_access(now);
+            history::push(
+                kp_entry,
+                previous,
+                &database.meta,
+                &database.header_attachments,
+            );
+        }
+
+        result
+    }
+
+    /// Record that an entry was used, e.g. its password was copied
+    ///
+    /// Updates the last access time and usage count without counting as a
+    /// modification.
+    pub fn record_access(&mut self, id: &str) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let kp_entry = Self::find_entry_mut(&mut database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+
+        kp_entry.times.set_last_access(keepass::db::Times::now());
+        kp_entry.times.usage_count += 1;
+
+        Ok(())
+    }
+
+    /// Get the previous versions of an entry, oldest first
+    pub fn get_entry_history(&self, id: &str) -> Result<Vec<Entry>> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        let (kp_entry, group_id) = Self::find_entry(&database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+
+        Ok(history::versions(kp_entry)
+            .iter()
+            .filter_map(|version| Self::convert_keepass_entry(version, &group_id))
+            .collect())
+    }
+
+    /// Restore the version at `index` in `get_entry_history`
+    ///
+    /// The current version is added to the history, so a restore can be undone.
+    pub fn restore_entry_version(&mut self, id: &str, index: usize) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let kp_entry = Self::find_entry_mut(&mut database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+
+        let mut restored = history::versions(kp_entry)
+            .get(index)
+            .cloned()
+            .ok_or_else(|| Self::history_item_not_found(id, index))?;
+
+        // Like KeePass, the restored version stays where the entry is now and
+        // counts as a new modification
+        let current = kp_entry.clone();
+        if let Some(time) = current.times.get_location_changed() {
+            restored.times.set_location_changed(*time);
+        }
+        restored
+            .times
+            .set_last_modification(keepass::db::Times::now());
+        restored.history = current.history.clone();
+        *kp_entry = restored;
+        history::push(
+            kp_entry,
+            current,
+            &database.meta,
+            &database.header_attachments,
+        );
+
+        Ok(())
+    }
+
+    /// Delete the version at `index` in `get_entry_history`
+    pub fn delete_history_item(&mut self, id: &str, index: usize) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let kp_entry = Self::find_entry_mut(&mut database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+
+        let mut versions = history::versions(kp_entry);
+        if index >= versions.len() {
+            return Err(Self::history_item_not_found(id, index));
+        }
+        versions.remove(index);
+        history::set_versions(kp_entry, versions);
+
+        Ok(())
+    }
+
+    fn history_item_not_found(id: &str, index: usize) -> VaultError {
+        VaultError::EntryNotFound(format!("History item {} of entry {}", index, id))
+    }
+
+    /// Move an entry to another group
+    pub fn move_entry(&mut self, id: &str, target_group: &str) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+
+        if Self::find_group_mut(&mut database.root, target_group).is_none() {
+            return Err(VaultError::GroupNotFound(target_group.to_string()));
+        }
+        if Self::find_entry(&database.root, id).is_none() {
+            return Err(VaultError::EntryNotFound(id.to_string()));
+        }
+        let parent = Self::parent_of(&database.root, id).expect("entry has a parent");
+        if parent.to_string() == target_group {
+            return Ok(());
+        }
+
+        let mut node = Self::find_and_remove_entry(&mut database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+        Self::record_move(&mut node, parent);
+        Self::find_group_mut(&mut database.root, target_group)
+            .expect("target group was checked")
+            .children
+            .push(node);
+
+        Ok(())
+    }
+
+    /// Move a group, with everything in it, to another parent group
+    ///
+    /// A group cannot be moved into itself or one of its subgroups.
+    pub fn move_group(&mut self, id: &str, target_parent: &str) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+
+        if database.root.uuid.to_string() == id {
+            return Err(VaultError::InvalidMove(
+                "Cannot move root group".to_string(),
+            ));
+        }
+        let group = Self::find_group_mut(&mut database.root, id)
+            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
+        if id == target_parent || Self::contains_item(group, target_parent) {
+            return Err(VaultError::InvalidMove(format!(
+                "Cannot move group {} into itself or its subgroup",
+                id
+            )));
+        }
+        if Self::find_group_mut(&mut database.root, target_parent).is_none() {
+            return Err(VaultError::GroupNotFound(target_parent.to_string()));
+        }
+
+        let parent = Self::parent_of(&database.root, id)
+            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
+        if parent.to_string() == target_parent {
+            return Ok(());
         }
 
+        let mut node = Self::find_and_remove_group(&mut database.root, id)
+            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
+        Self::record_move(&mut node, parent);
+        Self::find_group_mut(&mut database.root, target_parent)
+            .expect("target group was checked")
+            .children
+            .push(node);
+
         Ok(())
     }
 
+    /// Record that a node moved out of `previous_parent` just now
+    fn record_move(node: &mut keepass::db::Node, previous_parent: uuid::Uuid) {
+        let (times, custom_data) = match node {
+            keepass::db::Node::Entry(e) => (&mut e.times, &mut e.custom_data),
+            keepass::db::Node::Group(g) => (&mut g.times, &mut g.custom_data),
+        };
+
+        let now = keepass::db::Times::now();
+        times.set_location_changed(now);
+        custom_data.items.insert(
+            PREVIOUS_PARENT_KEY.to_string(),
+            keepass::db::CustomDataItem {
+                value: Some(keepass::db::Value::Unprotected(previous_parent.to_string())),
+                last_modification_time: Some(now),
+            },
+        );
+    }
+
+    /// Find the ID of the group containing an entry or group
+    fn parent_of(group: &keepass::db::Group, id: &str) -> Option<uuid::Uuid> {
+        for child in &group.children {
+            let (uuid, child_group) = match child {
+                keepass::db::Node::Entry(e) => (e.uuid, None),
+                keepass::db::Node::Group(g) => (g.uuid, Some(g)),
+            };
+            if uuid.to_string() == id {
+                return Some(group.uuid);
+            }
+            if let Some(found) = child_group.and_then(|g| Self::parent_of(g, id)) {
+                return Some(found);
+            }
+        }
+
+        None
+    }
+
     /// Delete an entry by moving it to the recycle bin
+    ///
+    /// Entries are deleted permanently if the recycle bin is disabled or
+    /// they already are in it.
     pub fn delete_entry(&mut self, id: &str) -> Result<()> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access_mut()?;
 
         let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
 
+        if !recycle_bin::is_enabled(database) || recycle_bin::contains(database, id) {
+            return Self::delete_entry_permanently(database, id);
+        }
+
         // Find the entry first
-        let entry_node = Self::find_and_remove_entry(&mut database.root, id)
+        let parent = Self::parent_of(&database.root, id)
             .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
-
-        // Get or create recycle bin
-        let recycle_bin = Self::find_or_create_recycle_bin(&mut database.root);
+        let mut entry_node = Self::find_and_remove_entry(&mut database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+        Self::record_move(&mut entry_node, parent);
 
         // Move to recycle bin
-        recycle_bin.children.push(entry_node);
+        recycle_bin::get_or_create(database)
+            .children
+            .push(entry_node);
 
         Ok(())
     }
 
     /// Permanently delete an entry (only works for items in recycle bin)
     pub fn permanently_delete_entry(&mut self, id: &str) -> Result<()> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access_mut()?;
 
         let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
 
         // Check if the entry is in the recycle bin
-        if !Self::is_in_recycle_bin(&database.root, id) {
+        if !recycle_bin::contains(database, id) {
             return Err(VaultError::EntryNotFound(format!(
                 "Entry {} is not in recycle bin. Move to recycle bin first.",
                 id
@@ -325,35 +1132,91 @@ impl Vault {
         }
 
         // Find and remove the entry permanently
-        if Self::remove_entry_from_group(&mut database.root, id) {
-            Ok(())
-        } else {
-            Err(VaultError::EntryNotFound(id.to_string()))
+        Self::delete_entry_permanently(database, id)
+    }
+
+    /// Remove an entry for good, recording it in `DeletedObjects`
+    fn delete_entry_permanently(database: &mut keepass::Database, id: &str) -> Result<()> {
+        let node = Self::find_and_remove_entry(&mut database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+        deleted_objects::record(
+            &mut database.deleted_objects,
+            &node,
+            keepass::db::Times::now(),
+        );
+        Ok(())
+    }
+
+    /// Restore an entry from the recycle bin to the group it was deleted from
+    ///
+    /// If that group no longer exists or is in the recycle bin itself, the
+    /// entry is restored to the root group.
+    pub fn restore_entry(&mut self, id: &str) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+
+        if !recycle_bin::contains(database, id) {
+            return Err(VaultError::EntryNotFound(format!(
+                "Entry {} is not in recycle bin",
+                id
+            )));
         }
+
+        let parent = Self::parent_of(&database.root, id).expect("entry has a parent");
+        let node = Self::find_and_remove_entry(&mut database.root, id)
+            .ok_or_else(|| VaultError::EntryNotFound(id.to_string()))?;
+        Self::restore_node(database, node, parent);
+
+        Ok(())
+    }
+
+    /// Put a node taken out of the recycle bin back where it came from
+    fn restore_node(
+        database: &mut keepass::Database,
+        mut node: keepass::db::Node,
+        parent: uuid::Uuid,
+    ) {
+        let custom_data = match &node {
+            keepass::db::Node::Entry(e) => &e.custom_data,
+            keepass::db::Node::Group(g) => &g.custom_data,
+        };
+        let target = recycle_bin::previous_parent(custom_data)
+            .filter(|uuid| {
+                let id = uuid.to_string();
+                recycle_bin::uuid(database) != Some(*uuid)
+                    && !recycle_bin::contains(database, &id)
+                    && Self::find_group_mut(&mut database.root, &id).is_some()
+            })
+            .unwrap_or(database.root.uuid);
+
+        Self::record_move(&mut node, parent);
+        Self::find_group_mut(&mut database.root, &target.to_string())
+            .expect("restore target exists")
+            .children
+            .push(node);
     }
 
     /// Get all groups from the database
     pub fn get_groups(&self) -> Result<Vec<Group>> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access()?;
 
         let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
         let mut groups = Vec::new();
 
         // Recursively collect all groups
-        Self::collect_groups_from_group(&database.root, None, &mut groups);
+        let recycle_bin = recycle_bin::uuid(database);
+        Self::collect_groups_from_group(&database.root, None, recycle_bin, &mut groups);
 
         Ok(groups)
     }
 
     /// Add a new group
     pub fn add_group(&mut self, group: Group) -> Result<String> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access_mut()?;
 
         let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let custom_icon = Self::resolve_custom_icon(database, group.custom_icon_id.as_deref())?;
 
         // Find parent group or use root
         let parent = if let Some(parent_id) = &group.parent_id {
@@ -364,7 +1227,10 @@ impl Vault {
         };
 
         // Create new keepass group
-        let mut kp_group = keepass::db::Group::default();
+        let mut kp_group = keepass::db::Group {
+            times: keepass::db::Times::new(),
+            ..Default::default()
+        };
 
         // Set UUID to match our group ID
         if let Ok(uuid) = uuid::Uuid::parse_str(&group.id) {
@@ -373,6 +1239,8 @@ impl Vault {
 
         kp_group.name = group.name.clone();
         kp_group.notes = Some(group.notes.clone());
+        kp_group.icon_id = Some(group.icon_id as usize);
+        kp_group.custom_icon_uuid = custom_icon;
 
         parent.add_child(kp_group);
 
@@ -380,28 +1248,44 @@ impl Vault {
     }
 
     /// Update a group
+    ///
+    /// `group.parent_id` is ignored, use `move_group` to change the parent.
     pub fn update_group(&mut self, id: &str, group: Group) -> Result<()> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access_mut()?;
 
         let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let custom_icon = Self::resolve_custom_icon(database, group.custom_icon_id.as_deref())?;
 
         // Find and update the group
         let kp_group = Self::find_group_mut(&mut database.root, id)
             .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
 
+        let notes = Some(group.notes.clone());
+        let icon_id = Some(group.icon_id as usize);
+        let changed = kp_group.name != group.name
+            || kp_group.notes != notes
+            || kp_group.icon_id != icon_id
+            || kp_group.custom_icon_uuid != custom_icon;
+
         kp_group.name = group.name.clone();
-        kp_group.notes = Some(group.notes.clone());
+        kp_group.notes = notes;
+        kp_group.icon_id = icon_id;
+        kp_group.custom_icon_uuid = custom_icon;
+        if changed {
+            kp_group
+                .times
+                .set_last_modification(keepass::db::Times::now());
+        }
 
         Ok(())
     }
 
     /// Delete a group by moving it to the recycle bin
+    ///
+    /// Groups are deleted permanently if the recycle bin is disabled, they
+    /// already are in it, or they are the recycle bin.
     pub fn delete_group(&mut self, id: &str) -> Result<()> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access_mut()?;
 
         let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
 
@@ -412,32 +1296,36 @@ impl Vault {
             ));
         }
 
-        // Find and remove the group
-        let group_node = Self::find_and_remove_group(&mut database.root, id)
-            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
-
-        // Check if this is the recycle bin itself
-        if let keepass::db::Node::Group(g) = &group_node {
-            if g.name == "Recycle Bin" || g.name == "回收站" {
-                // Don't move recycle bin to itself, just remove it
-                return Ok(());
+        let is_recycle_bin = recycle_bin::uuid(database).is_some_and(|bin| bin.to_string() == id);
+        if !recycle_bin::is_enabled(database)
+            || is_recycle_bin
+            || recycle_bin::contains(database, id)
+        {
+            Self::delete_group_permanently(database, id)?;
+            if is_recycle_bin {
+                recycle_bin::clear(database);
             }
+            return Ok(());
         }
 
-        // Get or create recycle bin
-        let recycle_bin = Self::find_or_create_recycle_bin(&mut database.root);
+        // Find and remove the group
+        let parent = Self::parent_of(&database.root, id)
+            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
+        let mut group_node = Self::find_and_remove_group(&mut database.root, id)
+            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
+        Self::record_move(&mut group_node, parent);
 
         // Move to recycle bin
-        recycle_bin.children.push(group_node);
+        recycle_bin::get_or_create(database)
+            .children
+            .push(group_node);
 
         Ok(())
     }
 
     /// Permanently delete a group (only works for items in recycle bin)
     pub fn permanently_delete_group(&mut self, id: &str) -> Result<()> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access_mut()?;
 
         let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
 
@@ -449,56 +1337,376 @@ impl Vault {
         }
 
         // Check if the group is in the recycle bin
-        if !Self::is_in_recycle_bin(&database.root, id) {
+        if !recycle_bin::contains(database, id) {
             return Err(VaultError::GroupNotFound(format!(
                 "Group {} is not in recycle bin. Move to recycle bin first.",
                 id
             )));
         }
 
-        if Self::remove_group_from_parent(&mut database.root, id) {
-            Ok(())
-        } else {
-            Err(VaultError::GroupNotFound(id.to_string()))
+        Self::delete_group_permanently(database, id)
+    }
+
+    /// Remove a group and everything in it for good, recording them in `DeletedObjects`
+    fn delete_group_permanently(database: &mut keepass::Database, id: &str) -> Result<()> {
+        let node = Self::find_and_remove_group(&mut database.root, id)
+            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
+        deleted_objects::record(
+            &mut database.deleted_objects,
+            &node,
+            keepass::db::Times::now(),
+        );
+        Ok(())
+    }
+
+    /// Restore a group, with everything in it, from the recycle bin
+    ///
+    /// If its previous parent no longer exists or is in the recycle bin
+    /// itself, the group is restored to the root group.
+    pub fn restore_group(&mut self, id: &str) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+
+        if !recycle_bin::contains(database, id) {
+            return Err(VaultError::GroupNotFound(format!(
+                "Group {} is not in recycle bin",
+                id
+            )));
         }
+
+        let parent = Self::parent_of(&database.root, id).expect("group has a parent");
+        let node = Self::find_and_remove_group(&mut database.root, id)
+            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
+        Self::restore_node(database, node, parent);
+
+        Ok(())
+    }
+
+    /// List the entries and groups in the recycle bin
+    pub fn get_recycle_bin_items(&self) -> Result<Vec<RecycledItem>> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        Ok(recycle_bin::items(database))
     }
 
     /// Empty the recycle bin
     pub fn empty_recycle_bin(&mut self) -> Result<()> {
-        if self.is_locked {
-            return Err(VaultError::VaultLocked);
-        }
+        self.check_access_mut()?;
 
         let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
 
         // Find recycle bin
-        if let Some(recycle_bin) = Self::find_recycle_bin(&mut database.root) {
-            recycle_bin.children.clear();
+        if recycle_bin::find(database).is_none() {
+            return Ok(());
+        }
+        let now = keepass::db::Times::now();
+        for node in std::mem::take(&mut recycle_bin::get_or_create(database).children) {
+            deleted_objects::record(&mut database.deleted_objects, &node, now);
         }
 
         Ok(())
     }
 
-    // Helper methods for traversing the database tree
-
-    /// Recursively collect entries from a group and its children
-    fn collect_entries_from_group(group: &keepass::db::Group, entries: &mut Vec<Entry>) {
-        // Process entries in this group
-        for entry in group.entries() {
-            if let Some(converted) = Self::convert_keepass_entry(entry, &group.uuid.to_string()) {
-                entries.push(converted);
-            }
-        }
+    /// List the permanently deleted entries and groups recorded for synchronization
+    pub fn get_deleted_objects(&self) -> Result<Vec<DeletedObject>> {
+        self.check_access()?;
 
-        // Recursively process child groups
-        for child in &group.children {
-            if let keepass::db::Node::Group(child_group) = child {
-                Self::collect_entries_from_group(child_group, entries);
-            }
-        }
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        Ok(database
+            .deleted_objects
+            .objects
+            .iter()
+            .map(DeletedObject::from)
+            .collect())
     }
 
-    /// Convert a keepass entry to our Entry structure
+    /// Shrink the deleted objects list
+    ///
+    /// Drops records of items that exist again and duplicate records. With
+    /// `max_age_days`, records of older deletions are dropped too; copies of
+    /// the vault that were not synchronized since then may bring those items
+    /// back. Returns the number of removed records.
+    pub fn compact_deleted_objects(&mut self, max_age_days: Option<u32>) -> Result<usize> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let cutoff = max_age_days
+            .map(|days| keepass::db::Times::now() - chrono::Duration::days(days.into()));
+        Ok(deleted_objects::compact(database, cutoff))
+    }
+
+    /// Check if deleted items are moved to the recycle bin
+    pub fn is_recycle_bin_enabled(&self) -> Result<bool> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        Ok(recycle_bin::is_enabled(database))
+    }
+
+    /// Turn the recycle bin on or off
+    ///
+    /// While it is off, deleting entries and groups is permanent. Items
+    /// already in the bin stay there.
+    pub fn set_recycle_bin_enabled(&mut self, enabled: bool) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        recycle_bin::set_enabled(database, enabled);
+
+        Ok(())
+    }
+
+    /// Set the retention policy applied to the recycle bin on every save
+    pub fn set_recycle_bin_policy(&mut self, policy: RecycleBinPolicy) {
+        self.recycle_bin_policy = policy;
+    }
+
+    /// Get the recycle bin retention policy
+    pub fn recycle_bin_policy(&self) -> &RecycleBinPolicy {
+        &self.recycle_bin_policy
+    }
+
+    /// Apply the recycle bin retention policy now
+    ///
+    /// Returns the number of permanently deleted items.
+    pub fn purge_recycle_bin(&mut self) -> Result<usize> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        Ok(recycle_bin::purge(
+            database,
+            &self.recycle_bin_policy,
+            keepass::db::Times::now(),
+        ))
+    }
+
+    /// ID of the recycle bin group, if it exists
+    pub fn recycle_bin_id(&self) -> Result<Option<String>> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        Ok(recycle_bin::uuid(database)
+            .filter(|uuid| !uuid.is_nil())
+            .map(|uuid| uuid.to_string()))
+    }
+
+    /// Get all custom icons
+    pub fn get_custom_icons(&self) -> Result<Vec<CustomIcon>> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+
+        Ok(database
+            .meta
+            .custom_icons
+            .icons
+            .iter()
+            .map(CustomIcon::from)
+            .collect())
+    }
+
+    /// Add a custom icon (usually a PNG image) and return its ID
+    ///
+    /// Adding the same image twice returns the existing icon. Icons nothing
+    /// refers to are removed when the vault is saved.
+    pub fn add_custom_icon(&mut self, data: Vec<u8>) -> Result<String> {
+        self.check_access_mut()?;
+
+        if data.is_empty() {
+            return Err(VaultError::InvalidEntry("Icon data is empty".to_string()));
+        }
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let icons = &mut database.meta.custom_icons.icons;
+
+        if let Some(existing) = icons.iter().find(|icon| icon.data == data) {
+            return Ok(existing.uuid.to_string());
+        }
+
+        let uuid = uuid::Uuid::new_v4();
+        icons.push(keepass::db::Icon { uuid, data });
+
+        Ok(uuid.to_string())
+    }
+
+    /// Remove a custom icon, resetting entries and groups using it
+    pub fn remove_custom_icon(&mut self, id: &str) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let uuid = icon::find_icon(database, id)
+            .ok_or_else(|| VaultError::IconNotFound(id.to_string()))?;
+
+        icon::remove_icon(database, uuid);
+
+        Ok(())
+    }
+
+    /// Assign a custom icon to an entry, or clear it with `None`
+    pub fn set_entry_custom_icon(&mut self, id: &str, icon_id: Option<&str>) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        let custom_icon = Self::resolve_custom_icon(database, icon_id)?;
+
+        self.change_entry(id, |kp_entry, _| {
+            kp_entry.custom_icon_uuid = custom_icon;
+            Ok(())
+        })
+    }
+
+    /// Assign a custom icon to a group, or clear it with `None`
+    pub fn set_group_custom_icon(&mut self, id: &str, icon_id: Option<&str>) -> Result<()> {
+        self.check_access_mut()?;
+
+        let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+        let custom_icon = Self::resolve_custom_icon(database, icon_id)?;
+
+        let kp_group = Self::find_group_mut(&mut database.root, id)
+            .ok_or_else(|| VaultError::GroupNotFound(id.to_string()))?;
+        if kp_group.custom_icon_uuid != custom_icon {
+            kp_group.custom_icon_uuid = custom_icon;
+            kp_group
+                .times
+                .set_last_modification(keepass::db::Times::now());
+        }
+
+        Ok(())
+    }
+
+    /// Get the attachments of an entry, sorted by name
+    pub fn get_attachments(&self, entry_id: &str) -> Result<Vec<Attachment>> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        let (kp_entry, _) = Self::find_entry(&database.root, entry_id)
+            .ok_or_else(|| VaultError::EntryNotFound(entry_id.to_string()))?;
+
+        Ok(attachment::list(database, kp_entry))
+    }
+
+    /// Read the content of an attachment
+    pub fn get_attachment(&self, entry_id: &str, name: &str) -> Result<Vec<u8>> {
+        self.check_access()?;
+
+        let database = self.database.as_ref().ok_or(VaultError::VaultLocked)?;
+        let (kp_entry, _) = Self::find_entry(&database.root, entry_id)
+            .ok_or_else(|| VaultError::EntryNotFound(entry_id.to_string()))?;
+
+        attachment::read(database, kp_entry, name)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| VaultError::AttachmentNotFound(name.to_string()))
+    }
+
+    /// Attach a file to an entry, replacing an attachment with the same name
+    ///
+    /// Set `protected` for content clients should keep in protected memory.
+    pub fn add_attachment(
+        &mut self,
+        entry_id: &str,
+        name: &str,
+        data: Vec<u8>,
+        protected: bool,
+    ) -> Result<()> {
+        Self::check_attachment_name(name)?;
+
+        let added = self.change_entry(entry_id, |kp_entry, binaries| {
+            Ok(attachment::attach(
+                binaries, kp_entry, name, data, protected,
+            ))
+        })?;
+
+        if let Some(hash) = added {
+            let database = self.database.as_mut().ok_or(VaultError::VaultLocked)?;
+            attachment::track(&mut database.meta, hash);
+        }
+
+        Ok(())
+    }
+
+    /// Rename an attachment
+    pub fn rename_attachment(&mut self, entry_id: &str, name: &str, new_name: &str) -> Result<()> {
+        Self::check_attachment_name(new_name)?;
+
+        self.change_entry(entry_id, |kp_entry, _| {
+            if !attachment::exists(kp_entry, name) {
+                return Err(VaultError::AttachmentNotFound(name.to_string()));
+            }
+            if name != new_name && attachment::exists(kp_entry, new_name) {
+                return Err(VaultError::InvalidEntry(format!(
+                    "Attachment {} already exists",
+                    new_name
+                )));
+            }
+
+            attachment::rename(kp_entry, name, new_name);
+            Ok(())
+        })
+    }
+
+    /// Remove an attachment from an entry
+    pub fn delete_attachment(&mut self, entry_id: &str, name: &str) -> Result<()> {
+        self.change_entry(entry_id, |kp_entry, _| {
+            if attachment::detach(kp_entry, name) {
+                Ok(())
+            } else {
+                Err(VaultError::AttachmentNotFound(name.to_string()))
+            }
+        })
+    }
+
+    fn check_attachment_name(name: &str) -> Result<()> {
+        if name.trim().is_empty() {
+            return Err(VaultError::InvalidEntry(
+                "Attachment name is empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that the vault is unlocked and record activity
+    fn check_access(&self) -> Result<()> {
+        if self.is_locked || self.auto_lock_expired() {
+            return Err(VaultError::VaultLocked);
+        }
+
+        self.last_activity.set(Instant::now());
+        Ok(())
+    }
+
+    /// Like `check_access`, but locks the vault if the policy has expired
+    fn check_access_mut(&mut self) -> Result<()> {
+        self.check_auto_lock();
+        self.check_access()
+    }
+
+    fn auto_lock_expired(&self) -> bool {
+        self.auto_lock
+            .is_expired(self.unlocked_at, self.last_activity.get(), Instant::now())
+    }
+
+    // Helper methods for traversing the database tree
+
+    /// Recursively collect entries from a group and its children
+    fn collect_entries_from_group(group: &keepass::db::Group, entries: &mut Vec<Entry>) {
+        // Process entries in this group
+        for entry in group.entries() {
+            if let Some(converted) = Self::convert_keepass_entry(entry, &group.uuid.to_string()) {
+                entries.push(converted);
+            }
+        }
+
+        // Recursively process child groups
+        for child in &group.children {
+            if let keepass::db::Node::Group(child_group) = child {
+                Self::collect_entries_from_group(child_group, entries);
+            }
+        }
+    }
+
+    /// Convert a keepass entry to our Entry structure
     fn convert_keepass_entry(kp_entry: &keepass::db::Entry, group_id: &str) -> Option<Entry> {
         let title = kp_entry.get_title().unwrap_or("");
         let username = kp_entry.get_username().unwrap_or("");
@@ -544,28 +1752,24 @@ impl Vault {
             }
         }
 
-        // Convert NaiveDateTime to DateTime<Utc>
-        let created_at = kp_entry
-            .times
-            .get_creation()
-            .map(|dt| chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(*dt, chrono::Utc))
-            .unwrap_or_else(|| chrono::Utc::now());
-
-        let modified_at = kp_entry
-            .times
-            .get_last_modification()
-            .map(|dt| chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(*dt, chrono::Utc))
-            .unwrap_or_else(|| chrono::Utc::now());
-
-        let accessed_at = kp_entry
-            .times
-            .get_last_access()
-            .map(|dt| chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(*dt, chrono::Utc))
-            .unwrap_or_else(|| chrono::Utc::now());
+        // Missing times fall back to the closest recorded one, never "now",
+        // so sorting by time stays stable across reloads
+        let time = |dt: Option<&chrono::NaiveDateTime>| {
+            dt.map(|dt| {
+                chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(*dt, chrono::Utc)
+            })
+        };
+        let created_at = time(kp_entry.times.get_creation())
+            .or_else(|| time(kp_entry.times.get_last_modification()))
+            .unwrap_or(chrono::DateTime::UNIX_EPOCH);
+        let modified_at = time(kp_entry.times.get_last_modification()).unwrap_or(created_at);
+        let accessed_at = time(kp_entry.times.get_last_access()).unwrap_or(modified_at);
 
+        // The expiry time is only meaningful while the Expires flag is set
         let expires_at = kp_entry
             .times
             .get_expiry()
+            .filter(|_| kp_entry.times.expires)
             .map(|dt| chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(*dt, chrono::Utc));
 
         Some(Entry {
@@ -576,21 +1780,98 @@ impl Vault {
             password: password.to_string(),
             url: url.to_string(),
             notes,
-            tags: Vec::new(), // KeePass doesn't have native tags in the same way
+            tags: Self::normalize_tags(&kp_entry.tags),
             totp_secret,
             custom_fields,
             created_at,
             modified_at,
             accessed_at,
+            usage_count: kp_entry.times.usage_count as u64,
             expires_at,
-            is_favorite: false, // Could be stored in a custom field
+            is_favorite: Self::is_favorite(kp_entry),
+            custom_icon_id: kp_entry.custom_icon_uuid.map(|uuid| uuid.to_string()),
         })
     }
 
+    /// Convert a time for the KDBX `Times` element, which stores whole seconds
+    fn kdbx_time(time: chrono::DateTime<chrono::Utc>) -> chrono::NaiveDateTime {
+        chrono::DateTime::from_timestamp(time.timestamp(), 0)
+            .unwrap_or(time)
+            .naive_utc()
+    }
+
+    /// Write an expiry time to the KDBX `Times` element
+    fn set_expiry(
+        times: &mut keepass::db::Times,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) {
+        match expires_at {
+            Some(expires_at) => {
+                times.set_expiry(Self::kdbx_time(expires_at));
+                times.expires = true;
+            }
+            None => times.expires = false,
+        }
+    }
+
+    /// Store the favorite flag as a CustomData item
+    fn set_favorite(kp_entry: &mut keepass::db::Entry, is_favorite: bool) {
+        if !is_favorite {
+            kp_entry.custom_data.items.remove(FAVORITE_KEY);
+        } else if !Self::is_favorite(kp_entry) {
+            kp_entry.custom_data.items.insert(
+                FAVORITE_KEY.to_string(),
+                keepass::db::CustomDataItem {
+                    value: Some(keepass::db::Value::Unprotected("true".to_string())),
+                    last_modification_time: Some(keepass::db::Times::now()),
+                },
+            );
+        }
+    }
+
+    fn is_favorite(kp_entry: &keepass::db::Entry) -> bool {
+        matches!(
+            kp_entry.custom_data.items.get(FAVORITE_KEY),
+            Some(keepass::db::CustomDataItem {
+                value: Some(keepass::db::Value::Unprotected(value)),
+                ..
+            }) if value == "true"
+        )
+    }
+
+    /// Look up a custom icon reference, failing if the icon does not exist
+    fn resolve_custom_icon(
+        database: &keepass::Database,
+        icon_id: Option<&str>,
+    ) -> Result<Option<uuid::Uuid>> {
+        icon_id
+            .map(|id| {
+                icon::find_icon(database, id)
+                    .ok_or_else(|| VaultError::IconNotFound(id.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Clean up tags for the KDBX `Tags` element
+    ///
+    /// KeePass separates tags with `;` or `,`, so tags containing them are
+    /// split. Tags are trimmed, and empty and duplicate tags dropped.
+    fn normalize_tags(tags: &[String]) -> Vec<String> {
+        let mut normalized: Vec<String> = Vec::new();
+        for tag in tags.iter().flat_map(|t| t.split([';', ','])) {
+            let tag = tag.trim();
+            if !tag.is_empty() && !normalized.iter().any(|t| t == tag) {
+                normalized.push(tag.to_string());
+            }
+        }
+        normalized
+    }
+
     /// Recursively collect groups from a group
     fn collect_groups_from_group(
         group: &keepass::db::Group,
         parent_id: Option<String>,
+        recycle_bin: Option<uuid::Uuid>,
         groups: &mut Vec<Group>,
     ) {
         let group_id = group.uuid.to_string();
@@ -599,16 +1880,22 @@ impl Vault {
             id: group_id.clone(),
             parent_id: parent_id.clone(),
             name: group.name.clone(),
-            icon_id: 0, // KeePass uses different icon system
+            icon_id: group.icon_id.map_or(48, |id| id as u32),
+            custom_icon_id: group.custom_icon_uuid.map(|uuid| uuid.to_string()),
             notes: group.notes.clone().unwrap_or_default(),
-            is_recycle_bin: false, // Could check group name
+            is_recycle_bin: Some(group.uuid) == recycle_bin,
             is_expanded: true,
         });
 
         // Recursively process children
         for child in &group.children {
             if let keepass::db::Node::Group(child_group) = child {
-                Self::collect_groups_from_group(child_group, Some(group_id.clone()), groups);
+                Self::collect_groups_from_group(
+                    child_group,
+                    Some(group_id.clone()),
+                    recycle_bin,
+                    groups,
+                );
             }
         }
     }
@@ -639,25 +1926,20 @@ impl Vault {
         None
     }
 
-    /// Find a mutable reference to an entry by ID
-    ///
-    /// This directly iterates over the children Vec<Node>, which avoids
-    /// the borrow checker issues with entries_mut()
-    fn find_entry_mut<'a>(
-        group: &'a mut keepass::db::Group,
+    /// Find an entry by ID, together with the ID of its group
+    fn find_entry<'a>(
+        group: &'a keepass::db::Group,
         id: &str,
-    ) -> Option<&'a mut keepass::db::Entry> {
-        // Search in this group's children
-        for child in &mut group.children {
+    ) -> Option<(&'a keepass::db::Entry, String)> {
+        for child in &group.children {
             match child {
                 keepass::db::Node::Entry(entry) => {
                     if entry.uuid.to_string() == id {
-                        return Some(entry);
+                        return Some((entry, group.uuid.to_string()));
                     }
                 }
                 keepass::db::Node::Group(child_group) => {
-                    // Recursively search in child groups
-                    if let Some(found) = Self::find_entry_mut(child_group, id) {
+                    if let Some(found) = Self::find_entry(child_group, id) {
                         return Some(found);
                     }
                 }
@@ -667,105 +1949,34 @@ impl Vault {
         None
     }
 
-    /// Remove an entry from a group by ID
-    fn remove_entry_from_group(group: &mut keepass::db::Group, id: &str) -> bool {
-        // Try to remove from this group's children
-        if let Some(pos) = group.children.iter().position(|child| {
-            if let keepass::db::Node::Entry(e) = child {
-                e.uuid.to_string() == id
-            } else {
-                false
-            }
-        }) {
-            group.children.remove(pos);
-            return true;
-        }
-
-        // Recursively try child groups
-        for child in &mut group.children {
-            if let keepass::db::Node::Group(child_group) = child {
-                if Self::remove_entry_from_group(child_group, id) {
-                    return true;
-                }
-            }
-        }
-
-        false
-    }
-
-    /// Remove a group from its parent (static method to avoid borrow checker issues)
-    fn remove_group_from_parent(group: &mut keepass::db::Group, id: &str) -> bool {
-        // Try to remove from children
-        if let Some(pos) = group.children.iter().position(|child| {
-            if let keepass::db::Node::Group(g) = child {
-                g.uuid.to_string() == id
-            } else {
-                false
-            }
-        }) {
-            group.children.remove(pos);
-            return true;
-        }
-
-        // Try recursively
-        for child in &mut group.children {
-            if let keepass::db::Node::Group(child_group) = child {
-                if Self::remove_group_from_parent(child_group, id) {
-                    return true;
-                }
-            }
-        }
-
-        false
-    }
-
-    /// Find the recycle bin group
-    fn find_recycle_bin<'a>(
+    /// Find a mutable reference to an entry by ID
+    ///
+    /// This directly iterates over the children Vec<Node>, which avoids
+    /// the borrow checker issues with entries_mut()
+    fn find_entry_mut<'a>(
         group: &'a mut keepass::db::Group,
-    ) -> Option<&'a mut keepass::db::Group> {
+        id: &str,
+    ) -> Option<&'a mut keepass::db::Entry> {
+        // Search in this group's children
         for child in &mut group.children {
             match child {
-                keepass::db::Node::Group(g) => {
-                    if g.name == "Recycle Bin" || g.name == "回收站" {
-                        return Some(g);
+                keepass::db::Node::Entry(entry) => {
+                    if entry.uuid.to_string() == id {
+                        return Some(entry);
                     }
+                }
+                keepass::db::Node::Group(child_group) => {
                     // Recursively search in child groups
-                    if let Some(found) = Self::find_recycle_bin(g) {
+                    if let Some(found) = Self::find_entry_mut(child_group, id) {
                         return Some(found);
                     }
                 }
-                _ => {}
             }
         }
 
         None
     }
 
-    /// Find or create the recycle bin group
-    fn find_or_create_recycle_bin<'a>(
-        root: &'a mut keepass::db::Group,
-    ) -> &'a mut keepass::db::Group {
-        // Check if recycle bin exists
-        let exists = root.children.iter().any(|child| {
-            if let keepass::db::Node::Group(g) = child {
-                g.name == "Recycle Bin" || g.name == "回收站"
-            } else {
-                false
-            }
-        });
-
-        if !exists {
-            // Create new recycle bin
-            let mut recycle_bin = keepass::db::Group::default();
-            recycle_bin.name = "Recycle Bin".to_string();
-            recycle_bin.notes = Some("Deleted items".to_string());
-            root.add_child(recycle_bin);
-        }
-
-        // Now find and return it
-        Self::find_recycle_bin(root).unwrap()
-    }
-
     /// Find and remove an entry, returning the Node
     fn find_and_remove_entry(
         group: &mut keepass::db::Group,
@@ -822,20 +2033,6 @@ impl Vault {
         None
     }
 
-    /// Check if an entry or group is in the recycle bin
-    fn is_in_recycle_bin(root: &keepass::db::Group, id: &str) -> bool {
-        // Find recycle bin
-        for child in &root.children {
-            if let keepass::db::Node::Group(g) = child {
-                if g.name == "Recycle Bin" || g.name == "回收站" {
-                    // Check if the item is in recycle bin
-                    return Self::contains_item(g, id);
-                }
-            }
-        }
-        false
-    }
-
     /// Check if a group contains an item (entry or group) with the given ID
     fn contains_item(group: &keepass::db::Group, id: &str) -> bool {
         for child in &group.children {
@@ -863,6 +2060,7 @@ impl Vault {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::fast_config;
     use tempfile::tempdir;
 
     #[test]
@@ -875,7 +2073,154 @@ mod tests {
 
         let vault = result.unwrap();
         assert!(!vault.is_locked());
-        assert_eq!(vault.path(), vault_path.as_path());
+        assert_eq!(vault.path(), Some(vault_path.as_path()));
+    }
+
+    #[test]
+    fn test_create_refuses_existing_vault() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+
+        Vault::create(&vault_path, "test123", config.clone()).unwrap();
+        assert!(matches!(
+            Vault::create(&vault_path, "other", config.clone()),
+            Err(VaultError::SaveError(_))
+        ));
+        assert!(Vault::open(&vault_path, "test123").is_ok());
+
+        let key = CompositeKey::from_password("other");
+        let storage = LocalFileStorage::new(&vault_path);
+        Vault::create_with_storage(storage, key, config, true).unwrap();
+        assert!(Vault::open(&vault_path, "other").is_ok());
+    }
+
+    #[test]
+    fn test_create_vault_with_config() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let config = VaultConfig {
+            cipher: crate::config::OuterCipher::ChaCha20,
+            ..fast_config()
+        };
+        Vault::create(&vault_path, "test123", config.clone()).unwrap();
+
+        // Settings are read back from the file header
+        let vault = Vault::open(&vault_path, "test123").unwrap();
+        assert_eq!(vault.config().unwrap(), config);
+    }
+
+    #[test]
+    fn test_in_memory_vault() {
+        let dir = tempdir().unwrap();
+        let config = fast_config();
+        let key = CompositeKey::from_password("test123");
+
+        let mut vault = Vault::create_in_memory(key.clone(), config).unwrap();
+        assert_eq!(vault.path(), None);
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let id = vault
+            .add_entry(Entry::new("In memory".to_string(), root_id))
+            .unwrap();
+
+        // There is no file to save to
+        assert!(matches!(vault.save(), Err(VaultError::SaveError(_))));
+
+        let data = vault.save_to_bytes().unwrap();
+        let mut copy = Vault::open_from_bytes(&data, key.clone()).unwrap();
+        assert_eq!(copy.get_entry(&id).unwrap().title, "In memory");
+
+        // Unlocking decrypts the last saved bytes
+        vault.lock();
+        vault.unlock(key.clone()).unwrap();
+        assert!(vault.get_entry(&id).is_ok());
+
+        let vault_path = dir.path().join("test.kdbx");
+        copy.save_as(&vault_path).unwrap();
+        assert_eq!(copy.path(), Some(vault_path.as_path()));
+        assert!(Vault::open_with_key(&vault_path, key)
+            .unwrap()
+            .get_entry(&id)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_set_config() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let config = fast_config();
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+
+        // Upgrade to Argon2id with a higher memory cost
+        let upgraded = VaultConfig {
+            argon2_memory: 32 * 1024,
+            ..VaultConfig::default()
+        };
+        vault.set_config(upgraded.clone()).unwrap();
+        vault.save().unwrap();
+        drop(vault);
+
+        let vault = Vault::open(&vault_path, "test123").unwrap();
+        assert_eq!(vault.config().unwrap(), upgraded);
+
+        // Invalid settings are rejected
+        let mut vault = vault;
+        let invalid = VaultConfig {
+            kdf_iterations: 0,
+            ..VaultConfig::default()
+        };
+        assert!(vault.set_config(invalid).is_err());
+    }
+
+    #[test]
+    fn test_change_credentials() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let mut vault = Vault::create(&vault_path, "test123", VaultConfig::default()).unwrap();
+
+        // The current key must be verified
+        assert!(matches!(
+            vault.change_credentials(
+                &CompositeKey::from_password("wrong"),
+                CompositeKey::from_password("new-pass")
+            ),
+            Err(VaultError::InvalidPassword)
+        ));
+
+        vault
+            .change_credentials(
+                &CompositeKey::from_password("test123"),
+                CompositeKey::from_password("new-pass"),
+            )
+            .unwrap();
+        vault.save().unwrap();
+        drop(vault);
+
+        assert!(Vault::open(&vault_path, "test123").is_err());
+        assert!(Vault::open(&vault_path, "new-pass").is_ok());
+    }
+
+    #[test]
+    fn test_open_with_key_file() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let key_path = dir.path().join("test.keyx");
+
+        crate::key::create_key_file(&key_path).unwrap();
+        let key = CompositeKey::from_password("test123")
+            .with_key_file(&key_path)
+            .unwrap();
+        Vault::create_with_key(&vault_path, key.clone(), VaultConfig::default()).unwrap();
+
+        // Both components are required
+        assert!(Vault::open(&vault_path, "test123").is_err());
+        let key_only = CompositeKey::new().with_key_file(&key_path).unwrap();
+        assert!(Vault::open_with_key(&vault_path, key_only).is_err());
+
+        assert!(Vault::open_with_key(&vault_path, key).is_ok());
     }
 
     #[test]
@@ -888,6 +2233,840 @@ mod tests {
 
         vault.lock();
         assert!(vault.is_locked());
+        assert!(vault.key.is_empty());
+    }
+
+    #[test]
+    fn test_unlock_vault() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let mut vault = Vault::create(&vault_path, "test123", VaultConfig::default()).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let entry_id = vault
+            .add_entry(Entry::new("Saved".to_string(), root_id))
+            .unwrap();
+        vault.save().unwrap();
+
+        vault.lock();
+        assert!(vault.get_entries().is_err());
+
+        // Wrong keys are rejected and the vault stays locked
+        assert!(matches!(
+            vault.unlock(CompositeKey::from_password("wrong")),
+            Err(VaultError::InvalidPassword)
+        ));
+        assert!(vault.is_locked());
+
+        vault
+            .unlock(CompositeKey::from_password("test123"))
+            .unwrap();
+        assert!(!vault.is_locked());
+        assert_eq!(vault.get_entry(&entry_id).unwrap().title, "Saved");
+    }
+
+    #[test]
+    fn test_unlock_keeps_unsaved_changes() {
+        let mut vault =
+            Vault::create_in_memory(CompositeKey::from_password("test123"), fast_config()).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let id = vault
+            .add_entry(Entry::new("Unsaved".to_string(), root_id))
+            .unwrap();
+
+        vault
+            .unlock(CompositeKey::from_password("test123"))
+            .unwrap();
+        assert!(vault.get_entry(&id).is_ok());
+        assert!(matches!(
+            vault.unlock(CompositeKey::from_password("wrong")),
+            Err(VaultError::InvalidPassword)
+        ));
+    }
+
+    #[test]
+    fn test_quick_unlock() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let mut vault = Vault::create(&vault_path, "test123", VaultConfig::default()).unwrap();
+        let config = QuickUnlockConfig {
+            max_attempts: 2,
+            argon2_memory: 64,
+            argon2_iterations: 1,
+        };
+        vault.enable_quick_unlock("1234", config).unwrap();
+
+        vault.lock();
+        vault.quick_unlock("1234").unwrap();
+        assert!(!vault.is_locked());
+
+        // Too many wrong PINs destroy the quick unlock material
+        vault.lock();
+        assert!(vault.quick_unlock("0000").is_err());
+        assert_eq!(vault.quick_unlock_attempts_remaining(), Some(1));
+        assert!(vault.quick_unlock("0000").is_err());
+        assert!(!vault.is_quick_unlock_enabled());
+        assert!(matches!(
+            vault.quick_unlock("1234"),
+            Err(VaultError::QuickUnlockUnavailable)
+        ));
+
+        vault
+            .unlock(CompositeKey::from_password("test123"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_auto_lock_idle_timeout() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let mut vault = Vault::create(&vault_path, "test123", VaultConfig::default()).unwrap();
+        vault.set_auto_lock_policy(AutoLockPolicy {
+            idle_timeout: Some(Duration::from_millis(200)),
+            session_timeout: None,
+            lock_on: vec![LockTrigger::AppBackgrounded],
+        });
+        assert!(vault.time_until_auto_lock().unwrap() <= Duration::from_millis(200));
+
+        // Activity keeps the vault open
+        std::thread::sleep(Duration::from_millis(120));
+        assert!(vault.get_entries().is_ok());
+        std::thread::sleep(Duration::from_millis(120));
+        assert!(vault.get_entries().is_ok());
+
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(vault.is_locked());
+        assert!(matches!(vault.get_entries(), Err(VaultError::VaultLocked)));
+        assert!(vault.check_auto_lock());
+        assert!(vault.key.is_empty());
+
+        // Platform events lock immediately
+        vault
+            .unlock(CompositeKey::from_password("test123"))
+            .unwrap();
+        assert!(!vault.handle_lock_trigger(LockTrigger::ScreenLocked));
+        assert!(vault.handle_lock_trigger(LockTrigger::AppBackgrounded));
+    }
+
+    #[test]
+    fn test_save_with_backups() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let mut vault = Vault::create(&vault_path, "test123", VaultConfig::default()).unwrap();
+        let policy = BackupPolicy {
+            max_backups: 2,
+            directory: None,
+        };
+        vault.set_backup_policy(Some(policy.clone()));
+
+        for _ in 0..3 {
+            vault.save().unwrap();
+        }
+
+        let backups = storage::list_backups(&vault_path, &policy).unwrap();
+        assert_eq!(backups.len(), 2);
+
+        // Backups are complete databases
+        drop(vault);
+        std::fs::copy(&backups[0], &vault_path).unwrap();
+        assert!(Vault::open(&vault_path, "test123").is_ok());
+    }
+
+    #[test]
+    fn test_save_refuses_external_changes() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let mut vault = Vault::create(&vault_path, "test123", VaultConfig::default()).unwrap();
+        let mut other = Vault::open(&vault_path, "test123").unwrap();
+        assert!(!vault.has_external_changes().unwrap());
+
+        // Another client saves first
+        let root_id = other.get_groups().unwrap()[0].id.clone();
+        other
+            .add_entry(Entry::new("Theirs".to_string(), root_id))
+            .unwrap();
+        other.save().unwrap();
+
+        assert!(vault.has_external_changes().unwrap());
+        assert!(matches!(
+            vault.save(),
+            Err(VaultError::ExternalModification(_))
+        ));
+
+        vault.force_save().unwrap();
+        assert!(!vault.has_external_changes().unwrap());
+        assert!(matches!(
+            other.save(),
+            Err(VaultError::ExternalModification(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_external_changes() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let mut vault = Vault::create(&vault_path, "test123", VaultConfig::default()).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let ours = vault
+            .add_entry(Entry::new("Ours".to_string(), root_id.clone()))
+            .unwrap();
+
+        let mut other = Vault::open(&vault_path, "test123").unwrap();
+        let theirs = other
+            .add_entry(Entry::new("Theirs".to_string(), root_id))
+            .unwrap();
+        other.save().unwrap();
+
+        let report = vault.merge_external_changes().unwrap();
+        assert_eq!(report.added, vec![theirs.clone()]);
+        vault.save().unwrap();
+
+        let reopened = Vault::open(&vault_path, "test123").unwrap();
+        assert!(reopened.get_entry(&ours).is_ok());
+        assert!(reopened.get_entry(&theirs).is_ok());
+    }
+
+    #[test]
+    fn test_foreign_attachments_survive_save() {
+        // A file from another client with an attachment we have no reference for
+        let key = CompositeKey::from_password("test123");
+        let db_config = fast_config()
+            .to_database_config(&keepass::config::DatabaseConfig::default())
+            .unwrap();
+        let mut database = keepass::Database::new(db_config);
+        database
+            .header_attachments
+            .push(keepass::db::HeaderAttachment {
+                flags: 0,
+                content: b"from another client".to_vec(),
+            });
+        let mut data = Vec::new();
+        database.save(&mut data, key.to_database_key()).unwrap();
+
+        let mut vault = Vault::open_from_bytes(&data, key.clone()).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let id = vault
+            .add_entry(Entry::new("Ours".to_string(), root_id))
+            .unwrap();
+        vault
+            .add_attachment(&id, "ours.txt", b"ours".to_vec(), false)
+            .unwrap();
+
+        let vault = Vault::open_from_bytes(&vault.save_to_bytes().unwrap(), key).unwrap();
+        let binaries = &vault.database.as_ref().unwrap().header_attachments;
+        assert_eq!(binaries.len(), 2);
+        assert!(binaries.iter().any(|b| b.content == b"from another client"));
+        assert_eq!(vault.get_attachment(&id, "ours.txt").unwrap(), b"ours");
+    }
+
+    #[test]
+    fn test_group_custom_icon_survives_merge() {
+        let key = CompositeKey::from_password("test123");
+        let mut vault = Vault::create_in_memory(key.clone(), fast_config()).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let now = keepass::db::Times::now();
+
+        let root = &mut vault.database.as_mut().unwrap().root;
+        root.times
+            .set_last_modification(now - chrono::Duration::hours(2));
+
+        // Their copy renamed the group before our icon change
+        let mut other = Vault::open_from_bytes(&vault.save_to_bytes().unwrap(), key).unwrap();
+        let theirs = &mut other.database.as_mut().unwrap().root;
+        theirs.name = "Renamed".to_string();
+        theirs
+            .times
+            .set_last_modification(now - chrono::Duration::hours(1));
+
+        let icon = vault.add_custom_icon(b"\x89PNG icon".to_vec()).unwrap();
+        vault.set_group_custom_icon(&root_id, Some(&icon)).unwrap();
+        vault.merge_from(&other).unwrap();
+
+        let group = &vault.get_groups().unwrap()[0];
+        assert_eq!(group.custom_icon_id, Some(icon));
+        assert_ne!(group.name, "Renamed");
+    }
+
+    #[test]
+    fn test_tags_persist() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+
+        let mut vault = Vault::create(&vault_path, "test123", VaultConfig::default()).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+
+        let mut entry = Entry::new("Mail".to_string(), root_id.clone());
+        entry.tags = vec![
+            "work".to_string(),
+            " email; personal ".to_string(),
+            String::new(),
+        ];
+        let mail = vault.add_entry(entry).unwrap();
+
+        let mut entry = Entry::new("Chat".to_string(), root_id);
+        entry.tags = vec!["work".to_string()];
+        let chat = vault.add_entry(entry).unwrap();
+        vault.delete_entry(&chat).unwrap();
+        vault.save().unwrap();
+
+        let vault = Vault::open(&vault_path, "test123").unwrap();
+        assert_eq!(
+            vault.get_entry(&mail).unwrap().tags,
+            vec!["work", "email", "personal"]
+        );
+        assert_eq!(
+            search::filter_by_tag(&vault.get_entries().unwrap(), "email").len(),
+            1
+        );
+
+        // The deleted entry's tag is not counted
+        let tags = vault.get_tags().unwrap();
+        assert_eq!(tags.len(), 3);
+        assert!(tags.iter().all(|t| t.count == 1));
+    }
+
+    #[test]
+    fn test_favorite_expiry_and_icon_persist() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+
+        let mut group = Group::new("Banking".to_string(), Some(root_id.clone()));
+        group.icon_id = 66;
+        let group_id = vault.add_group(group).unwrap();
+
+        let expires_at = chrono::DateTime::from_timestamp(2_000_000_000, 0).unwrap();
+        let mut entry = Entry::new("Bank".to_string(), group_id.clone());
+        entry.is_favorite = true;
+        entry.expires_at = Some(expires_at);
+        let bank = vault.add_entry(entry).unwrap();
+        let plain = vault
+            .add_entry(Entry::new("Plain".to_string(), root_id))
+            .unwrap();
+        vault.save().unwrap();
+
+        let mut vault = Vault::open(&vault_path, "test123").unwrap();
+        let entry = vault.get_entry(&bank).unwrap();
+        assert!(entry.is_favorite);
+        assert_eq!(entry.expires_at, Some(expires_at));
+        let group = vault
+            .get_groups()
+            .unwrap()
+            .into_iter()
+            .find(|g| g.id == group_id)
+            .unwrap();
+        assert_eq!(group.icon_id, 66);
+
+        let entry = vault.get_entry(&plain).unwrap();
+        assert!(!entry.is_favorite);
+        assert_eq!(entry.expires_at, None);
+
+        // Clearing both flags also round-trips
+        let mut entry = vault.get_entry(&bank).unwrap();
+        entry.is_favorite = false;
+        entry.expires_at = None;
+        vault.update_entry(&bank, entry).unwrap();
+        vault.save().unwrap();
+
+        let vault = Vault::open(&vault_path, "test123").unwrap();
+        let entry = vault.get_entry(&bank).unwrap();
+        assert!(!entry.is_favorite);
+        assert_eq!(entry.expires_at, None);
+    }
+
+    #[test]
+    fn test_custom_icons() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+
+        let favicon = vault.add_custom_icon(b"\x89PNG favicon".to_vec()).unwrap();
+        assert_eq!(
+            vault.add_custom_icon(b"\x89PNG favicon".to_vec()).unwrap(),
+            favicon
+        );
+        let unused = vault.add_custom_icon(b"\x89PNG unused".to_vec()).unwrap();
+        assert_eq!(vault.get_custom_icons().unwrap().len(), 2);
+
+        let mut entry = Entry::new("Site".to_string(), root_id.clone());
+        entry.custom_icon_id = Some(favicon.clone());
+        let site = vault.add_entry(entry).unwrap();
+        vault
+            .set_group_custom_icon(&root_id, Some(&favicon))
+            .unwrap();
+        assert!(matches!(
+            vault.set_entry_custom_icon(&site, Some(&uuid::Uuid::new_v4().to_string())),
+            Err(VaultError::IconNotFound(_))
+        ));
+
+        // The unreferenced icon is dropped on save
+        vault.save().unwrap();
+        let mut vault = Vault::open(&vault_path, "test123").unwrap();
+        let icons = vault.get_custom_icons().unwrap();
+        assert_eq!(icons.len(), 1);
+        assert_eq!(icons[0].id, favicon);
+        assert_eq!(icons[0].data, b"\x89PNG favicon");
+        assert!(icons.iter().all(|icon| icon.id != unused));
+        assert_eq!(
+            vault.get_entry(&site).unwrap().custom_icon_id,
+            Some(favicon.clone())
+        );
+        assert_eq!(
+            vault.get_groups().unwrap()[0].custom_icon_id,
+            Some(favicon.clone())
+        );
+
+        vault.remove_custom_icon(&favicon).unwrap();
+        assert_eq!(vault.get_entry(&site).unwrap().custom_icon_id, None);
+        assert_eq!(vault.get_groups().unwrap()[0].custom_icon_id, None);
+    }
+
+    #[test]
+    fn test_entry_history() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let mut entry = Entry::new("v1".to_string(), root_id);
+        entry.url = "https://example.com".to_string();
+        let id = vault.add_entry(entry).unwrap();
+
+        for title in ["v2", "v3"] {
+            let mut entry = vault.get_entry(&id).unwrap();
+            entry.title = title.to_string();
+            vault.update_entry(&id, entry).unwrap();
+        }
+        // Saving without changes adds no version
+        vault
+            .update_entry(&id, vault.get_entry(&id).unwrap())
+            .unwrap();
+
+        vault.save().unwrap();
+        let mut vault = Vault::open(&vault_path, "test123").unwrap();
+        let titles = |vault: &Vault| -> Vec<String> {
+            vault
+                .get_entry_history(&id)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.title.clone())
+                .collect()
+        };
+        assert_eq!(titles(&vault), vec!["v1", "v2"]);
+
+        // Backdate v1 so the restore is visibly a new modification
+        let old = chrono::DateTime::from_timestamp(1_500_000_000, 0).unwrap();
+        let kp_entry =
+            Vault::find_entry_mut(&mut vault.database.as_mut().unwrap().root, &id).unwrap();
+        let mut versions = history::versions(kp_entry);
+        versions[0].times.set_last_modification(old.naive_utc());
+        history::set_versions(kp_entry, versions);
+
+        vault.restore_entry_version(&id, 0).unwrap();
+        let restored = vault.get_entry(&id).unwrap();
+        assert_eq!(restored.title, "v1");
+        assert!(restored.modified_at > old);
+        assert_eq!(titles(&vault), vec!["v1", "v2", "v3"]);
+        assert_eq!(vault.get_entry_history(&id).unwrap()[0].modified_at, old);
+
+        vault.delete_history_item(&id, 0).unwrap();
+        assert_eq!(titles(&vault), vec!["v2", "v3"]);
+        assert!(matches!(
+            vault.delete_history_item(&id, 2),
+            Err(VaultError::EntryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_entry_times() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+
+        // Imported entries keep their original times
+        let created = chrono::DateTime::from_timestamp(1_500_000_000, 0).unwrap();
+        let mut entry = Entry::new("Old".to_string(), root_id);
+        entry.created_at = created;
+        entry.modified_at = created;
+        entry.accessed_at = created;
+        let id = vault.add_entry(entry).unwrap();
+        vault.save().unwrap();
+
+        let mut vault = Vault::open(&vault_path, "test123").unwrap();
+        let entry = vault.get_entry(&id).unwrap();
+        assert_eq!(entry.created_at, created);
+        assert_eq!(entry.modified_at, created);
+        assert_eq!(entry.usage_count, 0);
+
+        vault.record_access(&id).unwrap();
+        vault.record_access(&id).unwrap();
+        let entry = vault.get_entry(&id).unwrap();
+        assert_eq!(entry.usage_count, 2);
+        assert!(entry.accessed_at > created);
+        assert_eq!(entry.modified_at, created);
+        assert!(vault.get_entry_history(&id).unwrap().is_empty());
+
+        let mut changed = entry.clone();
+        changed.title = "Renamed".to_string();
+        vault.update_entry(&id, changed).unwrap();
+        let entry = vault.get_entry(&id).unwrap();
+        assert_eq!(entry.created_at, created);
+        assert!(entry.modified_at > created);
+    }
+
+    #[test]
+    fn test_attachments() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let id = vault
+            .add_entry(Entry::new("Server".to_string(), root_id))
+            .unwrap();
+
+        vault
+            .add_attachment(&id, "cert.pem", b"CERTIFICATE".to_vec(), false)
+            .unwrap();
+        vault
+            .add_attachment(&id, "recovery.pdf", b"%PDF".to_vec(), true)
+            .unwrap();
+        vault
+            .add_attachment(&id, "unused.txt", b"gone".to_vec(), false)
+            .unwrap();
+        vault.delete_attachment(&id, "unused.txt").unwrap();
+        vault
+            .rename_attachment(&id, "cert.pem", "server.pem")
+            .unwrap();
+        assert!(matches!(
+            vault.rename_attachment(&id, "server.pem", "recovery.pdf"),
+            Err(VaultError::InvalidEntry(_))
+        ));
+        // Each change kept the previous version
+        assert_eq!(vault.get_entry_history(&id).unwrap().len(), 5);
+
+        vault.save().unwrap();
+        let vault = Vault::open(&vault_path, "test123").unwrap();
+        let attachments = vault.get_attachments(&id).unwrap();
+        assert_eq!(
+            attachments,
+            vec![
+                Attachment {
+                    name: "recovery.pdf".to_string(),
+                    size: 4,
+                    protected: true,
+                },
+                Attachment {
+                    name: "server.pem".to_string(),
+                    size: 11,
+                    protected: false,
+                },
+            ]
+        );
+        assert_eq!(
+            vault.get_attachment(&id, "server.pem").unwrap(),
+            b"CERTIFICATE"
+        );
+        assert!(matches!(
+            vault.get_attachment(&id, "cert.pem"),
+            Err(VaultError::AttachmentNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolved_entry() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let mut entry = Entry::new("Server".to_string(), root_id.clone());
+        entry.password = "hunter2".to_string();
+        let server = vault.add_entry(entry).unwrap();
+
+        let mut entry = Entry::new("Alias".to_string(), root_id);
+        entry.password = format!("{{REF:P@I:{}}}", server.replace('-', ""));
+        entry.url = "https://example.com/login".to_string();
+        let alias = vault.add_entry(entry).unwrap();
+
+        let resolved = vault.get_resolved_entry(&alias).unwrap();
+        assert_eq!(resolved.password, "hunter2");
+        assert!(vault
+            .get_entry(&alias)
+            .unwrap()
+            .password
+            .starts_with("{REF:"));
+        assert_eq!(
+            vault
+                .resolve_placeholders(&alias, "{TITLE} on {URL:HOST}")
+                .unwrap(),
+            "Alias on example.com"
+        );
+    }
+
+    #[test]
+    fn test_move_entry_and_group() {
+        let config = fast_config();
+        let mut vault =
+            Vault::create_in_memory(CompositeKey::from_password("test123"), config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+
+        let a = vault
+            .add_group(Group::new("A".to_string(), Some(root_id.clone())))
+            .unwrap();
+        let b = vault
+            .add_group(Group::new("B".to_string(), Some(a.clone())))
+            .unwrap();
+        let c = vault
+            .add_group(Group::new("C".to_string(), Some(root_id)))
+            .unwrap();
+        let id = vault
+            .add_entry(Entry::new("Moved".to_string(), a.clone()))
+            .unwrap();
+
+        vault.move_entry(&id, &c).unwrap();
+        assert_eq!(vault.get_entry(&id).unwrap().group_id, c);
+        let database = vault.database.as_ref().unwrap();
+        let (kp_entry, _) = Vault::find_entry(&database.root, &id).unwrap();
+        assert_eq!(
+            kp_entry.custom_data.items[PREVIOUS_PARENT_KEY].value,
+            Some(keepass::db::Value::Unprotected(a.clone()))
+        );
+
+        assert!(matches!(
+            vault.move_group(&a, &b),
+            Err(VaultError::InvalidMove(_))
+        ));
+        assert!(matches!(
+            vault.move_entry(&id, "missing"),
+            Err(VaultError::GroupNotFound(_))
+        ));
+
+        vault.move_group(&a, &c).unwrap();
+        let groups = vault.get_groups().unwrap();
+        let parent = |id: &str| {
+            groups
+                .iter()
+                .find(|g| g.id == id)
+                .unwrap()
+                .parent_id
+                .clone()
+        };
+        assert_eq!(parent(&a), Some(c.clone()));
+        assert_eq!(parent(&b), Some(a));
+    }
+
+    #[test]
+    fn test_recycle_bin() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        assert_eq!(vault.recycle_bin_id().unwrap(), None);
+
+        let first = vault
+            .add_entry(Entry::new("First".to_string(), root_id.clone()))
+            .unwrap();
+        vault.delete_entry(&first).unwrap();
+        let bin_id = vault.recycle_bin_id().unwrap().unwrap();
+        let mut bin = vault
+            .get_groups()
+            .unwrap()
+            .into_iter()
+            .find(|g| g.id == bin_id)
+            .unwrap();
+        assert!(bin.is_recycle_bin);
+
+        // A renamed bin is still the bin after reopening
+        bin.name = "Papierkorb".to_string();
+        vault.update_group(&bin_id, bin).unwrap();
+        vault.save().unwrap();
+        let mut vault = Vault::open(&vault_path, "test123").unwrap();
+        assert_eq!(vault.recycle_bin_id().unwrap(), Some(bin_id.clone()));
+        let second = vault
+            .add_entry(Entry::new("Second".to_string(), root_id.clone()))
+            .unwrap();
+        vault.delete_entry(&second).unwrap();
+        assert_eq!(vault.get_entry(&second).unwrap().group_id, bin_id);
+
+        // Without a bin, deletes are permanent
+        vault.set_recycle_bin_enabled(false).unwrap();
+        assert!(!vault.is_recycle_bin_enabled().unwrap());
+        let third = vault
+            .add_entry(Entry::new("Third".to_string(), root_id))
+            .unwrap();
+        vault.delete_entry(&third).unwrap();
+        assert!(vault.get_entry(&third).is_err());
+    }
+
+    #[test]
+    fn test_restore_from_recycle_bin() {
+        let config = fast_config();
+        let mut vault =
+            Vault::create_in_memory(CompositeKey::from_password("test123"), config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let work = vault
+            .add_group(Group::new("Work".to_string(), Some(root_id.clone())))
+            .unwrap();
+        let mail = vault
+            .add_entry(Entry::new("Mail".to_string(), work.clone()))
+            .unwrap();
+        let vpn = vault
+            .add_entry(Entry::new("VPN".to_string(), work.clone()))
+            .unwrap();
+
+        vault.delete_entry(&mail).unwrap();
+        let items = vault.get_recycle_bin_items().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "Mail");
+        assert!(!items[0].is_group);
+        assert!(items[0].deleted_at.is_some());
+        assert_eq!(items[0].previous_parent_id, Some(work.clone()));
+
+        vault.restore_entry(&mail).unwrap();
+        assert_eq!(vault.get_entry(&mail).unwrap().group_id, work);
+        assert!(vault.get_recycle_bin_items().unwrap().is_empty());
+        assert!(matches!(
+            vault.restore_entry(&mail),
+            Err(VaultError::EntryNotFound(_))
+        ));
+
+        // Entries whose group is gone come back to the root group
+        vault.delete_entry(&mail).unwrap();
+        vault.delete_group(&work).unwrap();
+        vault.restore_entry(&mail).unwrap();
+        assert_eq!(vault.get_entry(&mail).unwrap().group_id, root_id);
+
+        vault.restore_group(&work).unwrap();
+        assert_eq!(vault.get_entry(&vpn).unwrap().group_id, work);
+        assert!(vault.get_recycle_bin_items().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recycle_bin_policy() {
+        let config = fast_config();
+        let mut vault =
+            Vault::create_in_memory(CompositeKey::from_password("test123"), config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let mut ids = Vec::new();
+        for title in ["First", "Second", "Third"] {
+            let id = vault
+                .add_entry(Entry::new(title.to_string(), root_id.clone()))
+                .unwrap();
+            vault.delete_entry(&id).unwrap();
+            ids.push(id);
+        }
+
+        vault.set_recycle_bin_policy(RecycleBinPolicy {
+            max_age_days: Some(30),
+            max_items: Some(2),
+        });
+        let data = vault.save_to_bytes().unwrap();
+        assert_eq!(vault.get_recycle_bin_items().unwrap().len(), 2);
+        assert_eq!(vault.purge_recycle_bin().unwrap(), 0);
+
+        let vault = Vault::open_from_bytes(&data, CompositeKey::from_password("test123")).unwrap();
+        assert_eq!(vault.get_recycle_bin_items().unwrap().len(), 2);
+        let database = vault.database.as_ref().unwrap();
+        assert!(database.deleted_objects.contains(ids[0].parse().unwrap()));
+    }
+
+    #[test]
+    fn test_permanent_deletes_are_recorded() {
+        let config = fast_config();
+        let mut vault =
+            Vault::create_in_memory(CompositeKey::from_password("test123"), config).unwrap();
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let group = vault
+            .add_group(Group::new("Old".to_string(), Some(root_id.clone())))
+            .unwrap();
+        let inner = vault
+            .add_entry(Entry::new("Inner".to_string(), group.clone()))
+            .unwrap();
+        let single = vault
+            .add_entry(Entry::new("Single".to_string(), root_id.clone()))
+            .unwrap();
+        let other = vault
+            .add_entry(Entry::new("Other".to_string(), root_id))
+            .unwrap();
+
+        vault.delete_entry(&single).unwrap();
+        vault.permanently_delete_entry(&single).unwrap();
+        vault.delete_group(&group).unwrap();
+        vault.permanently_delete_group(&group).unwrap();
+        vault.delete_entry(&other).unwrap();
+        vault.empty_recycle_bin().unwrap();
+
+        let data = vault.save_to_bytes().unwrap();
+        let mut vault =
+            Vault::open_from_bytes(&data, CompositeKey::from_password("test123")).unwrap();
+        let mut deleted: Vec<String> = vault
+            .get_deleted_objects()
+            .unwrap()
+            .into_iter()
+            .map(|object| object.id)
+            .collect();
+        deleted.sort();
+        let mut expected = vec![group, inner, single, other];
+        expected.sort();
+        assert_eq!(deleted, expected);
+
+        assert_eq!(vault.compact_deleted_objects(None).unwrap(), 0);
+        assert_eq!(vault.compact_deleted_objects(Some(1)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_metadata_persists() {
+        let dir = tempdir().unwrap();
+        let vault_path = dir.path().join("test.kdbx");
+        let config = fast_config();
+        let mut vault = Vault::create(&vault_path, "test123", config).unwrap();
+
+        let mut metadata = vault.get_metadata().unwrap();
+        assert_eq!(metadata.name, "");
+        metadata.name = "Work".to_string();
+        metadata.description = "Shared team vault".to_string();
+        metadata.default_username = "admin".to_string();
+        metadata.color = Some("#3366CC".to_string());
+        metadata.maintenance_history_days = Some(90);
+        metadata.master_key_change_rec = Some(180);
+        vault.set_metadata(&metadata).unwrap();
+        vault
+            .change_credentials(
+                &CompositeKey::from_password("test123"),
+                CompositeKey::from_password("new-password"),
+            )
+            .unwrap();
+        vault.save().unwrap();
+
+        let vault = Vault::open(&vault_path, "new-password").unwrap();
+        let saved = vault.get_metadata().unwrap();
+        assert!(saved.master_key_changed.is_some());
+        assert_eq!(
+            saved,
+            VaultMetadata {
+                master_key_changed: saved.master_key_changed,
+                ..metadata
+            }
+        );
     }
 
     #[test]
@@ -0,0 +1,337 @@
+// WebDAV storage module
+//
+// Stores the vault on a WebDAV server (Nextcloud, ownCloud, ...). Conflicts are
+// detected with ETags: saves send `If-Match`, so the server refuses to replace
+// a vault that changed since it was loaded.
+
+use crate::crypto::CryptoUtils;
+use crate::error::{Result, VaultError};
+use crate::storage::{Revision, VaultStorage};
+use base64::Engine;
+use std::fmt;
+use std::io::Read;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Vault stored on a WebDAV server
+pub struct WebDavStorage {
+    url: String,
+    authorization: Option<Zeroizing<String>>,
+    allow_insecure_auth: bool,
+    agent: ureq::Agent,
+}
+
+impl WebDavStorage {
+    /// Store the vault at `url` (e.g. `https://dav.example.com/team.kdbx`)
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            authorization: None,
+            allow_insecure_auth: false,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(60))
+                .build(),
+        }
+    }
+
+    /// Authenticate with HTTP basic auth
+    ///
+    /// Credentials are only sent over `https://` unless
+    /// `allow_insecure_auth()` is set.
+    pub fn with_credentials(mut self, username: &str, password: &str) -> Self {
+        let credentials = Zeroizing::new(format!("{}:{}", username, password));
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials.as_bytes());
+        self.authorization = Some(Zeroizing::new(format!("Basic {}", encoded)));
+        self
+    }
+
+    /// Send basic auth credentials over plain `http://` (e.g. on a trusted LAN)
+    pub fn allow_insecure_auth(mut self) -> Self {
+        self.allow_insecure_auth = true;
+        self
+    }
+
+    fn request(&self, method: &str) -> Result<ureq::Request> {
+        let request = self.agent.request(method, &self.url);
+        match &self.authorization {
+            Some(_) if !self.allow_insecure_auth && !is_https(&self.url) => {
+                Err(VaultError::InvalidConfig(format!(
+                    "Refusing to send credentials to {} without TLS",
+                    self.url
+                )))
+            }
+            Some(authorization) => Ok(request.set("Authorization", authorization)),
+            None => Ok(request),
+        }
+    }
+
+    fn error(&self, action: &str, err: ureq::Error) -> String {
+        match err {
+            ureq::Error::Status(status, response) => format!(
+                "Failed to {} {}: {} {}",
+                action,
+                self.url,
+                status,
+                response.status_text()
+            ),
+            ureq::Error::Transport(e) => format!("Failed to {} {}: {}", action, self.url, e),
+        }
+    }
+}
+
+impl fmt::Debug for WebDavStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebDavStorage")
+            .field("url", &self.url)
+            .field("authorization", &self.authorization.as_ref().map(|_| "***"))
+            .field("allow_insecure_auth", &self.allow_insecure_auth)
+            .finish()
+    }
+}
+
+impl VaultStorage for WebDavStorage {
+    fn location(&self) -> String {
+        self.url.clone()
+    }
+
+    fn load(&mut self) -> Result<(Vec<u8>, Revision)> {
+        let response = self
+            .request("GET")?
+            .call()
+            .map_err(|e| VaultError::OpenError(self.error("download", e)))?;
+
+        let revision = response_revision(&response);
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut data)
+            .map_err(|e| VaultError::OpenError(format!("Failed to download vault: {}", e)))?;
+
+        let revision = revision.unwrap_or_else(|| content_revision(&data));
+        Ok((data, revision))
+    }
+
+    fn revision(&self) -> Result<Option<Revision>> {
+        match self.request("HEAD")?.call() {
+            Ok(response) => match response_revision(&response) {
+                Some(revision) => Ok(Some(revision)),
+                // Without validators the content has to be compared
+                None => {
+                    let mut data = Vec::new();
+                    self.request("GET")?
+                        .call()
+                        .map_err(|e| VaultError::OpenError(self.error("download", e)))?
+                        .into_reader()
+                        .read_to_end(&mut data)?;
+                    Ok(Some(content_revision(&data)))
+                }
+            },
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(VaultError::OpenError(self.error("check", e))),
+        }
+    }
+
+    fn save(&mut self, data: &[u8], expected: Option<&Revision>) -> Result<Revision> {
+        let mut request = self
+            .request("PUT")?
+            .set("Content-Type", "application/octet-stream");
+
+        match expected {
+            // Let the server check strong ETags atomically
+            Some(revision) if revision.as_str().starts_with('"') => {
+                request = request.set("If-Match", revision.as_str());
+            }
+            // Weak ETags and dates need a separate check
+            Some(revision) if self.has_changed(revision)? => {
+                return Err(VaultError::ExternalModification(self.location()));
+            }
+            _ => {}
+        }
+
+        match request.send_bytes(data) {
+            Ok(response) => match response_revision(&response) {
+                Some(revision) => Ok(revision),
+                None => self.revision()?.ok_or_else(|| {
+                    VaultError::SaveError(format!("{} is missing after upload", self.url))
+                }),
+            },
+            Err(ureq::Error::Status(412, _)) => {
+                Err(VaultError::ExternalModification(self.location()))
+            }
+            Err(e) => Err(VaultError::SaveError(self.error("upload", e))),
+        }
+    }
+}
+
+/// Revision from the ETag or Last-Modified header
+fn response_revision(response: &ureq::Response) -> Option<Revision> {
+    response
+        .header("ETag")
+        .or_else(|| response.header("Last-Modified"))
+        .map(Revision::new)
+}
+
+fn is_https(url: &str) -> bool {
+    url.get(..8)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"))
+}
+
+fn content_revision(data: &[u8]) -> Revision {
+    let hash: String = CryptoUtils::sha256(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Revision::new(format!("sha256:{}", hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::fast_config;
+    use crate::{CompositeKey, Entry, Vault};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Minimal WebDAV stand-in serving a single file with ETags
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/vault.kdbx", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            // Stored file and its version
+            let mut file: Option<(Vec<u8>, u32)> = None;
+
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let method = request_line
+                    .split(' ')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+
+                let (mut length, mut if_match) = (0, None);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let (name, value) = line.split_once(": ").unwrap();
+                    match name.to_ascii_lowercase().as_str() {
+                        "content-length" => length = value.parse().unwrap(),
+                        "if-match" => if_match = Some(value.to_string()),
+                        _ => {}
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let etag = |version: u32| format!("\"v{}\"", version);
+                let (status, etag_header, content) = match (method.as_str(), file.as_ref()) {
+                    ("GET" | "HEAD", None) => ("404 Not Found", String::new(), Vec::new()),
+                    ("GET", Some((data, v))) => ("200 OK", etag(*v), data.clone()),
+                    ("HEAD", Some((data, v))) => ("200 OK", etag(*v), vec![0; data.len()]),
+                    ("PUT", current) => {
+                        let current_etag = current.map(|(_, v)| etag(*v));
+                        if if_match.is_some() && if_match != current_etag {
+                            ("412 Precondition Failed", String::new(), Vec::new())
+                        } else {
+                            let version = current.map_or(1, |(_, v)| v + 1);
+                            file = Some((body, version));
+                            ("201 Created", etag(version), Vec::new())
+                        }
+                    }
+                    _ => ("405 Method Not Allowed", String::new(), Vec::new()),
+                };
+
+                let mut response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    content.len()
+                );
+                if !etag_header.is_empty() {
+                    response.push_str(&format!("ETag: {}\r\n", etag_header));
+                }
+                response.push_str("\r\n");
+                stream.write_all(response.as_bytes()).unwrap();
+                if method != "HEAD" {
+                    stream.write_all(&content).unwrap();
+                }
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    fn test_save_checks_etag() {
+        let url = serve();
+        let mut ours = WebDavStorage::new(&url);
+        let mut theirs = WebDavStorage::new(&url);
+
+        assert_eq!(ours.revision().unwrap(), None);
+        let first = ours.save(b"first", None).unwrap();
+
+        let (data, revision) = theirs.load().unwrap();
+        assert_eq!(data, b"first");
+        assert_eq!(revision, first);
+
+        // They save first, so our save against the old ETag is refused
+        theirs.save(b"theirs", Some(&revision)).unwrap();
+        assert!(ours.has_changed(&first).unwrap());
+        assert!(matches!(
+            ours.save(b"ours", Some(&first)),
+            Err(VaultError::ExternalModification(_))
+        ));
+        assert_eq!(ours.load().unwrap().0, b"theirs");
+    }
+
+    #[test]
+    fn test_credentials_require_tls() {
+        let url = serve();
+        let mut storage = WebDavStorage::new(&url).with_credentials("alice", "secret");
+        assert!(matches!(
+            storage.save(b"data", None),
+            Err(VaultError::InvalidConfig(_))
+        ));
+        assert!(storage.load().is_err());
+
+        let mut storage = storage.allow_insecure_auth();
+        storage.save(b"data", None).unwrap();
+        assert_eq!(storage.load().unwrap().0, b"data");
+
+        assert!(is_https("HTTPS://dav.example.com/vault.kdbx"));
+        assert!(!is_https("http://dav.example.com/vault.kdbx"));
+    }
+
+    #[test]
+    fn test_vault_over_webdav() {
+        let url = serve();
+        let key = CompositeKey::from_password("test123");
+        let config = fast_config();
+
+        let mut vault =
+            Vault::create_with_storage(WebDavStorage::new(&url), key.clone(), config, false)
+                .unwrap();
+        assert_eq!(vault.path(), None);
+        let root_id = vault.get_groups().unwrap()[0].id.clone();
+        let id = vault
+            .add_entry(Entry::new("Remote".to_string(), root_id))
+            .unwrap();
+        vault.save().unwrap();
+
+        let other = Vault::open_with_storage(WebDavStorage::new(&url), key.clone()).unwrap();
+        assert_eq!(other.get_entry(&id).unwrap().title, "Remote");
+        assert!(!vault.has_external_changes().unwrap());
+
+        // The vault exists now, so creating it again needs an explicit overwrite
+        assert!(matches!(
+            Vault::create_with_storage(WebDavStorage::new(&url), key, fast_config(), false),
+            Err(VaultError::SaveError(_))
+        ));
+    }
+}